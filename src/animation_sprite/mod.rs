@@ -38,6 +38,11 @@ impl AnimationBundle {
             animation_timer
         }
     }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.sprite.color = color;
+        self
+    }
 }
 
 fn animate_sprite(
@@ -0,0 +1,220 @@
+//! Plays games with a bot without opening a window and reports how they went,
+//! or ranks all bots over the same seeds with `--tournament`.
+//! Spawns and merges are the game's own, so numbers match what players see.
+
+use bevy_2048_p::cli::{parse_mode, parse_size};
+use bevy_2048_p::game::rules::WinTarget;
+use bevy_2048_p::game::simulation::{play_game, ranking_csv, ranking_json, ranking_table, tournament, Bot, Report, SimConfig};
+
+const USAGE: &str = "Usage: bevy_2048_sim [--games <n>] [--seed <first seed>] [--bot <random|greedy|corner|expectimax>] [--depth <n>] [--size <n>|<width>x<height>] [--mode <classic|relaxed|hardcore|kids|autoplay|evil>] [--target <512|2048|4096|none>] [--keep-going] [--format <csv|json|table>] [--tournament [--depths <n,n,..>] [--threads <n>]]";
+const DEFAULT_GAMES: u64 = 100;
+const DEFAULT_DEPTH: u32 = 2;
+const DEFAULT_TOURNAMENT_DEPTHS: [u32; 3] = [1, 2, 3];
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Format {
+    Csv,
+    Json,
+    Table,
+}
+
+/// Every bot plays the same seeds, expectimax once for every depth
+#[derive(Clone, PartialEq, Debug)]
+struct Tournament {
+    depths: Vec<u32>,
+    threads: usize,
+}
+
+impl Tournament {
+    fn bots(&self) -> Vec<Bot> {
+        let expectimax = self.depths.iter().filter_map(|&depth| Bot::from_name("expectimax", depth));
+        [Bot::Random, Bot::Greedy, Bot::Corner].into_iter().chain(expectimax).collect()
+    }
+}
+
+/// Games to play, seeds go up from `first_seed`
+#[derive(PartialEq, Debug)]
+struct SimArgs {
+    games: u64,
+    first_seed: u64,
+    config: SimConfig,
+    /// CSV for a single bot and table for tournament, when not given
+    format: Option<Format>,
+    tournament: Option<Tournament>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return;
+    }
+
+    let SimArgs { games, first_seed, config, format, tournament: tournament_args } = parse_args(args).unwrap_or_else(|e| {
+        eprintln!("{e}\n{USAGE}");
+        std::process::exit(2);
+    });
+    let seeds = first_seed..first_seed.saturating_add(games);
+
+    if let Some(tournament_args) = tournament_args {
+        let reports = tournament(&config, &tournament_args.bots(), seeds, tournament_args.threads);
+        match format.unwrap_or(Format::Table) {
+            Format::Csv => print!("{}", ranking_csv(&reports)),
+            Format::Json => print!("{}", ranking_json(&reports)),
+            Format::Table => print!("{}", ranking_table(&reports)),
+        }
+        return;
+    }
+
+    let records: Vec<_> = seeds.map(|seed| play_game(&config, seed)).collect();
+    let report = Report::new(&config, &records);
+    match format.unwrap_or(Format::Csv) {
+        Format::Csv => print!("{}", report.to_csv()),
+        Format::Json => print!("{}", report.to_json()),
+        Format::Table => print!("{}", ranking_table(&[report])),
+    }
+}
+
+/// Parses `--flag value` and `--flag=value` arguments
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<SimArgs, String> {
+    let mut sim_args = SimArgs {
+        games: DEFAULT_GAMES,
+        first_seed: 0,
+        config: SimConfig::default(),
+        format: None,
+        tournament: None,
+    };
+    let mut bot_name = sim_args.config.bot.name().to_string();
+    let mut depth = DEFAULT_DEPTH;
+    let mut tournament = false;
+    let mut depths = DEFAULT_TOURNAMENT_DEPTHS.to_vec();
+    let mut threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let inline_value = value.is_some();
+        let mut value = || value.clone().or_else(|| args.next()).ok_or(format!("Missing value for {flag}"));
+
+        match flag.as_str() {
+            "--games" => {
+                let value = value()?;
+                sim_args.games = value.parse().map_err(|_| format!("Invalid number of games: {value}"))?;
+            }
+            "--seed" => {
+                let value = value()?;
+                sim_args.first_seed = value.parse().map_err(|_| format!("Invalid seed: {value}"))?;
+            }
+            "--bot" => bot_name = value()?,
+            "--depth" => {
+                let value = value()?;
+                depth = value.parse().ok().filter(|&depth| depth > 0).ok_or(format!("Invalid depth: {value}"))?;
+            }
+            "--size" => {
+                let size = parse_size(&value()?)?;
+                sim_args.config.width = size.width;
+                sim_args.config.height = size.height;
+            }
+            "--mode" => sim_args.config.mode = parse_mode(&value()?)?,
+            "--target" => sim_args.config.target = parse_target(&value()?)?,
+            "--keep-going" if !inline_value => sim_args.config.keep_going = true,
+            "--format" => {
+                sim_args.format = Some(match value()?.to_ascii_lowercase().as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    "table" => Format::Table,
+                    other => return Err(format!("Unknown format: {other}")),
+                })
+            }
+            "--tournament" if !inline_value => tournament = true,
+            "--depths" => {
+                let value = value()?;
+                depths = value
+                    .split(',')
+                    .map(|depth| depth.trim().parse().ok().filter(|&depth| depth > 0))
+                    .collect::<Option<_>>()
+                    .ok_or(format!("Invalid depths: {value}"))?;
+            }
+            "--threads" => {
+                let value = value()?;
+                threads = value.parse().ok().filter(|&threads| threads > 0).ok_or(format!("Invalid number of threads: {value}"))?;
+            }
+            _ => return Err(format!("Unknown argument: {flag}")),
+        }
+    }
+
+    sim_args.config.bot = Bot::from_name(&bot_name, depth)
+        .ok_or(format!("Unknown bot: {bot_name}, should be one of {}", Bot::NAMES.join(", ")))?;
+    if tournament {
+        sim_args.tournament = Some(Tournament { depths, threads });
+    }
+    Ok(sim_args)
+}
+
+fn parse_target(value: &str) -> Result<WinTarget, String> {
+    WinTarget::ALL
+        .into_iter()
+        .find(|target| target.name().eq_ignore_ascii_case(value))
+        .ok_or(format!("Unknown target: {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_2048_p::game::ai::AiConfig;
+    use bevy_2048_p::game::mode::GameMode;
+
+    fn parse(args: &[&str]) -> Result<SimArgs, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let defaults = parse(&[]).unwrap();
+        assert_eq!(defaults.games, DEFAULT_GAMES);
+        assert_eq!(defaults.config, SimConfig::default());
+        assert_eq!(defaults.format, None);
+        assert_eq!(defaults.tournament, None);
+
+        assert_eq!(
+            parse(&["--games", "10", "--seed=5", "--bot", "expectimax", "--depth", "3", "--size", "5x4", "--mode=kids", "--target", "none", "--keep-going", "--format", "json"]),
+            Ok(SimArgs {
+                games: 10,
+                first_seed: 5,
+                config: SimConfig {
+                    width: 5,
+                    height: 4,
+                    mode: GameMode::Kids,
+                    target: WinTarget(None),
+                    bot: Bot::Expectimax(AiConfig { depth: 3, time_budget: None, ..AiConfig::default() }),
+                    keep_going: true,
+                },
+                format: Some(Format::Json),
+                tournament: None,
+            })
+        );
+
+        let tournament = parse(&["--tournament", "--depths", "1,3", "--threads=2"]).unwrap().tournament.unwrap();
+        assert_eq!(tournament, Tournament { depths: vec![1, 3], threads: 2 });
+        assert_eq!(
+            tournament.bots().iter().map(Bot::label).collect::<Vec<_>>(),
+            vec!["random", "greedy", "corner", "expectimax-1", "expectimax-3"]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_args_test() {
+        assert!(parse(&["--games", "many"]).is_err());
+        assert!(parse(&["--bot", "oracle"]).is_err());
+        assert!(parse(&["--depth", "0"]).is_err());
+        assert!(parse(&["--target", "1000"]).is_err());
+        assert!(parse(&["--format", "xml"]).is_err());
+        assert!(parse(&["--keep-going=yes"]).is_err());
+        assert!(parse(&["--size", "9"]).is_err());
+        assert!(parse(&["--depths", "1,,2"]).is_err());
+        assert!(parse(&["--threads", "0"]).is_err());
+    }
+}
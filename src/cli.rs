@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+use bevy::prelude::*;
+use crate::game::autoplay::AutoplaySettings;
+use crate::game::mode::GameMode;
+use crate::game::playback::{replay_frames, LoadedReplay};
+use crate::game::replay::Replay;
+use crate::game::{BoardSize, SeedSetting, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+
+pub const USAGE: &str = "Usage: bevy_2048_p [--seed <number>] [--size <n>|<width>x<height>] [--mode <classic|relaxed|hardcore|kids|autoplay|evil>] [--replay <file>] [--autoplay-delay <seconds>] [--turbo]";
+
+/// Options given on the command line, override settings for new games
+#[derive(Default, PartialEq, Debug)]
+pub struct CliArgs {
+    pub seed: Option<u64>,
+    pub size: Option<BoardSize>,
+    pub mode: Option<GameMode>,
+    /// Replay file to watch instead of playing
+    pub replay: Option<PathBuf>,
+    /// Pause before every move of the bot, in seconds
+    pub autoplay_delay: Option<f32>,
+    /// Bot moves without animation or delay
+    pub turbo: bool,
+}
+
+impl CliArgs {
+    /// Arguments of the process, prints usage and exits on `--help` or invalid arguments
+    pub fn from_env() -> Self {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            println!("{USAGE}");
+            std::process::exit(0);
+        }
+
+        parse_args(args).unwrap_or_else(|e| {
+            eprintln!("{e}\n{USAGE}");
+            std::process::exit(2);
+        })
+    }
+
+    /// Inserts resources for given options, prints error and exits when replay can't be loaded
+    pub fn insert_resources(&self, app: &mut App) {
+        if let Some(seed) = self.seed {
+            app.insert_resource(SeedSetting(Some(seed)));
+        }
+        if let Some(size) = self.size {
+            app.insert_resource(size);
+        }
+        if let Some(mode) = self.mode {
+            app.insert_resource(mode);
+        }
+        if let Some(path) = &self.replay {
+            let replay = read_replay(path).unwrap_or_else(|e| {
+                eprintln!("Replay {} can't be loaded: {e}", path.display());
+                std::process::exit(2);
+            });
+            // Watched with "Watch replay" from the main menu
+            app.insert_resource(LoadedReplay(Some(replay)));
+        }
+        if self.autoplay_delay.is_some() || self.turbo {
+            let mut settings = AutoplaySettings {
+                turbo: self.turbo,
+                ..default()
+            };
+            if let Some(delay) = self.autoplay_delay {
+                settings.delay = delay;
+            }
+            app.insert_resource(settings);
+        }
+    }
+}
+
+/// Parses `--flag value` and `--flag=value` arguments
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<CliArgs, String> {
+    let mut cli_args = CliArgs::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let inline_value = value.is_some();
+        let mut value = || value.clone().or_else(|| args.next()).ok_or(format!("Missing value for {flag}"));
+
+        match flag.as_str() {
+            "--seed" => {
+                let value = value()?;
+                let seed = value.parse().map_err(|_| format!("Invalid seed: {value}"))?;
+                cli_args.seed = Some(seed);
+            }
+            "--size" => cli_args.size = Some(parse_size(&value()?)?),
+            "--mode" => cli_args.mode = Some(parse_mode(&value()?)?),
+            "--replay" => cli_args.replay = Some(PathBuf::from(value()?)),
+            "--autoplay-delay" => {
+                let value = value()?;
+                let delay = value.parse().ok().filter(|delay: &f32| delay.is_finite() && *delay >= 0.);
+                cli_args.autoplay_delay = Some(delay.ok_or(format!("Invalid delay: {value}"))?);
+            }
+            "--turbo" if !inline_value => cli_args.turbo = true,
+            "--turbo" => return Err("--turbo takes no value".to_string()),
+            _ => return Err(format!("Unknown argument: {flag}")),
+        }
+    }
+
+    Ok(cli_args)
+}
+
+/// Replay from file, checked to play out on the board
+fn read_replay(path: &PathBuf) -> Result<Replay, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let replay = contents.parse::<Replay>()?;
+    replay_frames(&replay)?;
+
+    Ok(replay)
+}
+
+/// Size as `4` for square board or `5x4` for width and height
+pub fn parse_size(value: &str) -> Result<BoardSize, String> {
+    let invalid = || format!("Invalid size: {value}");
+    let (width, height) = match value.split_once('x') {
+        Some((width, height)) => (width.parse().map_err(|_| invalid())?, height.parse().map_err(|_| invalid())?),
+        None => {
+            let size = value.parse().map_err(|_| invalid())?;
+            (size, size)
+        }
+    };
+
+    let range = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+    if !range.contains(&width) || !range.contains(&height) {
+        return Err(format!("Size should be from {MIN_BOARD_SIZE} to {MAX_BOARD_SIZE}: {value}"));
+    }
+
+    Ok(BoardSize { width, height })
+}
+
+pub fn parse_mode(value: &str) -> Result<GameMode, String> {
+    GameMode::ALL
+        .into_iter()
+        .find(|mode| mode.name().eq_ignore_ascii_case(value))
+        .ok_or(format!("Unknown mode: {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_args_test() {
+        assert_eq!(parse(&[]), Ok(CliArgs::default()));
+        assert_eq!(
+            parse(&["--seed", "42", "--size", "5x3", "--mode", "hardcore"]),
+            Ok(CliArgs {
+                seed: Some(42),
+                size: Some(BoardSize { width: 5, height: 3 }),
+                mode: Some(GameMode::Hardcore),
+                replay: None,
+                autoplay_delay: None,
+                turbo: false,
+            })
+        );
+        assert_eq!(
+            parse(&["--size=6", "--seed=18446744073709551615", "--mode=Kids", "--replay=best.replay"]),
+            Ok(CliArgs {
+                seed: Some(u64::MAX),
+                size: Some(BoardSize { width: 6, height: 6 }),
+                mode: Some(GameMode::Kids),
+                replay: Some(PathBuf::from("best.replay")),
+                autoplay_delay: None,
+                turbo: false,
+            })
+        );
+        assert_eq!(
+            parse(&["--mode", "autoplay", "--turbo", "--autoplay-delay=0.5"]),
+            Ok(CliArgs {
+                mode: Some(GameMode::Autoplay),
+                autoplay_delay: Some(0.5),
+                turbo: true,
+                ..CliArgs::default()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_invalid_args_test() {
+        assert!(parse(&["--seed"]).is_err());
+        assert!(parse(&["--seed", "-1"]).is_err());
+        assert!(parse(&["--size", "2"]).is_err());
+        assert!(parse(&["--size", "4x9"]).is_err());
+        assert!(parse(&["--size", "4by4"]).is_err());
+        assert!(parse(&["--mode", "impossible"]).is_err());
+        assert!(parse(&["--speed", "1"]).is_err());
+        assert!(parse(&["--autoplay-delay", "-1"]).is_err());
+        assert!(parse(&["--turbo=yes"]).is_err());
+    }
+}
@@ -0,0 +1,521 @@
+use std::time::Duration;
+use bevy::platform::time::Instant;
+use bevy::prelude::*;
+use crate::game::bitboard::Bitboard;
+use crate::game::components::{Board, Direction};
+use crate::game::engine::DIRECTIONS;
+
+/// Chance of spawned tile being 4, the same one in five that [crate::game::engine::random_spawn] draws
+const BIG_SPAWN_PROBABILITY: f64 = 0.2;
+/// Spawns less likely than that together are not looked into, board is evaluated as is
+const MIN_PROBABILITY: f64 = 0.0001;
+/// Taken from evaluation of the board where no move is left
+const LOSS_PENALTY: f64 = 10_000.;
+/// Search time of [SteppedSearch] in every frame, about half of a frame at 60 fps
+pub const FRAME_SEARCH_TIME: Duration = Duration::from_millis(8);
+
+/// Weights of board features in evaluation, values are powers of two
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Heuristics {
+    /// Penalty for rows and columns going up and down instead of one way
+    pub monotonicity: f64,
+    /// Bonus for every empty tile
+    pub empty: f64,
+    /// Penalty for difference between neighbour tiles
+    pub smoothness: f64,
+    /// Bonus for the biggest tile sitting in a corner, per its value
+    pub corner: f64,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Self {
+            monotonicity: 1.,
+            empty: 2.7,
+            smoothness: 0.1,
+            corner: 1.,
+        }
+    }
+}
+
+/// Settings of expectimax search
+#[derive(Resource, Copy, Clone, PartialEq, Debug)]
+pub struct AiConfig {
+    pub heuristics: Heuristics,
+    /// Moves to look ahead, each followed by a spawn
+    pub depth: u32,
+    /// Time after which deeper search stops, result of the last finished depth is used
+    pub time_budget: Option<Duration>,
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        Self {
+            heuristics: Heuristics::default(),
+            depth: 3,
+            time_budget: Some(Duration::from_millis(250)),
+        }
+    }
+}
+
+/// Recommended move
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Hint {
+    pub direction: Direction,
+    /// Evaluation of the board expected after the move
+    pub expected_value: f64,
+    /// Depth of the search that gave the hint
+    pub depth: u32,
+}
+
+/// Board the search can run on, values are powers of two with 0 for empty tiles
+pub trait SearchBoard: Sized {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn value_at(&self, index: usize) -> usize;
+    /// Board after the move, `None` when the move doesn't change it
+    fn after_move(&self, direction: Direction) -> Option<Self>;
+    fn empty_tiles(&self) -> impl Iterator<Item = usize> + '_;
+    fn with_tile(&self, index: usize, value: usize) -> Self;
+}
+
+impl SearchBoard for Bitboard {
+    fn width(&self) -> usize {
+        4
+    }
+
+    fn height(&self) -> usize {
+        4
+    }
+
+    fn value_at(&self, index: usize) -> usize {
+        self.value(index)
+    }
+
+    fn after_move(&self, direction: Direction) -> Option<Self> {
+        let (moved, _) = self.apply_move(direction);
+        (moved != *self).then_some(moved)
+    }
+
+    fn empty_tiles(&self) -> impl Iterator<Item = usize> + '_ {
+        self.empty_indices()
+    }
+
+    fn with_tile(&self, index: usize, value: usize) -> Self {
+        self.with_value(index, value)
+    }
+}
+
+impl SearchBoard for Board<usize> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn value_at(&self, index: usize) -> usize {
+        self[index].unwrap_or(0)
+    }
+
+    fn after_move(&self, direction: Direction) -> Option<Self> {
+        let mut moved = self.clone();
+        moved.apply_move(direction).changed.then_some(moved)
+    }
+
+    fn empty_tiles(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter().enumerate().filter(|(_, value)| value.is_none()).map(|(index, _)| index)
+    }
+
+    fn with_tile(&self, index: usize, value: usize) -> Self {
+        let mut board = self.clone();
+        board[index] = Some(value);
+        board
+    }
+}
+
+/// Best move for the board, searched on [Bitboard] when the board fits into it
+pub fn search(values: &Board<usize>, config: &AiConfig) -> Option<Hint> {
+    match Bitboard::from_board(values) {
+        Some(bitboard) => best_move(&bitboard, config),
+        None => best_move(values, config),
+    }
+}
+
+/// Best move by expectimax over spawns, `None` when no move is left
+///
+/// Search goes deeper one move at a time until [AiConfig::depth] or [AiConfig::time_budget] is reached,
+/// the first depth is always finished.
+pub fn best_move<B: SearchBoard + Clone>(board: &B, config: &AiConfig) -> Option<Hint> {
+    DeepeningSearch::new(board, config).step(None).expect("Search without a slice should run to the end")
+}
+
+/// [search] done a bit at a time, for searches sharing the thread with frames
+pub enum SteppedSearch {
+    Bitboard(DeepeningSearch<Bitboard>),
+    Board(DeepeningSearch<Board<usize>>),
+}
+
+impl SteppedSearch {
+    pub fn new(values: &Board<usize>, config: &AiConfig) -> Self {
+        match Bitboard::from_board(values) {
+            Some(bitboard) => Self::Bitboard(DeepeningSearch::new(&bitboard, config)),
+            None => Self::Board(DeepeningSearch::new(values, config)),
+        }
+    }
+
+    /// Searches for about `slice`, result of the search once it's done
+    pub fn step(&mut self, slice: Duration) -> Option<Option<Hint>> {
+        match self {
+            Self::Bitboard(search) => search.step(Some(slice)),
+            Self::Board(search) => search.step(Some(slice)),
+        }
+    }
+}
+
+/// Iterative deepening of [best_move], split into a search of every spawn after every move
+///
+/// Only time spent in [DeepeningSearch::step] counts towards [AiConfig::time_budget].
+pub struct DeepeningSearch<B> {
+    config: AiConfig,
+    /// Boards after the moves that change the board, with their empty tiles
+    moves: Vec<(Direction, B, Vec<usize>)>,
+    depth: u32,
+    /// Move and spawn after it searched next at this depth
+    next_move: usize,
+    next_spawn: usize,
+    /// Sum over the spawns searched so far after the move
+    total: f64,
+    /// Best move at this depth so far
+    depth_best: Option<Hint>,
+    /// Best move of the last finished depth
+    best: Option<Hint>,
+    spent: Duration,
+}
+
+impl<B: SearchBoard + Clone> DeepeningSearch<B> {
+    pub fn new(board: &B, config: &AiConfig) -> Self {
+        let moves = DIRECTIONS
+            .into_iter()
+            .filter_map(|direction| board.after_move(direction).map(|moved| (direction, moved)))
+            .map(|(direction, moved)| {
+                let empty = moved.empty_tiles().collect();
+                (direction, moved, empty)
+            })
+            .collect();
+
+        Self {
+            config: *config,
+            moves,
+            depth: 1,
+            next_move: 0,
+            next_spawn: 0,
+            total: 0.,
+            depth_best: None,
+            best: None,
+            spent: Duration::ZERO,
+        }
+    }
+
+    /// Searches until `slice` is over or the search is done, result of the search once it's done
+    ///
+    /// Slice is checked between spawns, a spawn is searched to the end once started.
+    pub fn step(&mut self, slice: Option<Duration>) -> Option<Option<Hint>> {
+        let start = Instant::now();
+        let deadline = self.config.time_budget.map(|budget| start + budget.saturating_sub(self.spent));
+        let result = self.search_until(slice.map(|slice| start + slice), deadline);
+        self.spent += start.elapsed();
+
+        result
+    }
+
+    fn search_until(&mut self, slice_end: Option<Instant>, deadline: Option<Instant>) -> Option<Option<Hint>> {
+        if self.moves.is_empty() {
+            return Some(None);
+        }
+
+        loop {
+            let search = Search {
+                heuristics: &self.config.heuristics,
+                deadline: deadline.filter(|_| self.depth > 1),
+            };
+            let (direction, moved, empty) = &self.moves[self.next_move];
+            // Spawns of 2 and 4 on every empty tile, none at the last depth
+            let spawns = if self.depth > 1 { empty.len() * 2 } else { 0 };
+
+            if self.next_spawn < spawns {
+                let index = empty[self.next_spawn / 2];
+                let (value, chance) = [(1, 1. - BIG_SPAWN_PROBABILITY), (2, BIG_SPAWN_PROBABILITY)][self.next_spawn % 2];
+                let tile_probability = 1. / empty.len() as f64;
+                match search.max_node(&moved.with_tile(index, value), self.depth - 1, tile_probability * chance) {
+                    Ok(value) => self.total += chance * value,
+                    Err(OutOfTime) => return Some(self.best),
+                }
+                self.next_spawn += 1;
+            } else {
+                let expected_value = if spawns == 0 {
+                    evaluate(moved, search.heuristics)
+                } else {
+                    self.total / empty.len() as f64
+                };
+                if self.depth_best.is_none_or(|hint| expected_value > hint.expected_value) {
+                    self.depth_best = Some(Hint { direction: *direction, expected_value, depth: self.depth });
+                }
+
+                self.next_move += 1;
+                self.next_spawn = 0;
+                self.total = 0.;
+                if self.next_move == self.moves.len() {
+                    self.best = self.depth_best.take();
+                    if self.depth >= self.config.depth.max(1) {
+                        return Some(self.best);
+                    }
+                    self.depth += 1;
+                    self.next_move = 0;
+                }
+            }
+
+            if slice_end.is_some_and(|slice_end| Instant::now() > slice_end) {
+                return None;
+            }
+        }
+    }
+}
+
+/// Evaluation of the board at rest, bigger is better
+pub fn evaluate<B: SearchBoard>(board: &B, heuristics: &Heuristics) -> f64 {
+    let (width, height) = (board.width(), board.height());
+    let lines = (0..height)
+        .map(|row| (0..width).map(|col| board.value_at(col + row * width)).collect::<Vec<_>>())
+        .chain((0..width).map(|col| (0..height).map(|row| board.value_at(col + row * width)).collect()));
+
+    let mut monotonicity = 0.;
+    let mut smoothness = 0.;
+    for line in lines {
+        let (mut increase, mut decrease) = (0., 0.);
+        for pair in line.windows(2) {
+            let (first, second) = (pair[0] as f64, pair[1] as f64);
+            if first < second {
+                increase += second - first;
+            } else {
+                decrease += first - second;
+            }
+            if pair[0] != 0 && pair[1] != 0 {
+                smoothness += (first - second).abs();
+            }
+        }
+        monotonicity += f64::min(increase, decrease);
+    }
+
+    let empty = board.empty_tiles().count() as f64;
+    let max_value = (0..width * height).map(|index| board.value_at(index)).max().unwrap_or(0);
+    let corners = [0, width - 1, (height - 1) * width, width * height - 1];
+    let corner = if corners.iter().any(|&index| board.value_at(index) == max_value) { max_value as f64 } else { 0. };
+
+    heuristics.empty * empty + heuristics.corner * corner
+        - heuristics.monotonicity * monotonicity
+        - heuristics.smoothness * smoothness
+}
+
+/// Spawn the player can do the least about, as index and value, `None` when the board is full
+///
+/// Minimax over the player's best replies: every spawn of 2 and 4 is tried, then the best move after it,
+/// `depth` times. Ties go to the first empty tile and the smaller value.
+pub fn worst_spawn<B: SearchBoard>(board: &B, depth: u32, heuristics: &Heuristics) -> Option<(usize, usize)> {
+    let mut worst: Option<((usize, usize), f64)> = None;
+
+    for index in board.empty_tiles() {
+        for value in [1, 2] {
+            let bound = worst.map_or(f64::INFINITY, |(_, worst)| worst);
+            let reply = best_reply(&board.with_tile(index, value), depth.max(1), heuristics, f64::NEG_INFINITY, bound);
+            if reply < bound {
+                worst = Some(((index, value), reply));
+            }
+        }
+    }
+
+    worst.map(|(spawn, _)| spawn)
+}
+
+/// Value of the player's best move, alpha-beta bounded
+fn best_reply<B: SearchBoard>(board: &B, depth: u32, heuristics: &Heuristics, alpha: f64, beta: f64) -> f64 {
+    let mut best = f64::NEG_INFINITY;
+
+    for direction in DIRECTIONS {
+        let Some(moved) = board.after_move(direction) else {
+            continue;
+        };
+        let value = if depth <= 1 {
+            evaluate(&moved, heuristics)
+        } else {
+            worst_reply(&moved, depth - 1, heuristics, alpha.max(best), beta)
+        };
+        best = best.max(value);
+        if best >= beta {
+            break;
+        }
+    }
+
+    if best == f64::NEG_INFINITY {
+        evaluate(board, heuristics) - LOSS_PENALTY
+    } else {
+        best
+    }
+}
+
+/// Value after the spawn that is worst for the player, alpha-beta bounded
+fn worst_reply<B: SearchBoard>(board: &B, depth: u32, heuristics: &Heuristics, alpha: f64, beta: f64) -> f64 {
+    let mut worst = f64::INFINITY;
+
+    for index in board.empty_tiles() {
+        for value in [1, 2] {
+            worst = worst.min(best_reply(&board.with_tile(index, value), depth, heuristics, alpha, beta.min(worst)));
+            if worst <= alpha {
+                return worst;
+            }
+        }
+    }
+
+    // Moves always leave an empty tile, this is only for completeness
+    if worst == f64::INFINITY {
+        best_reply(board, depth, heuristics, alpha, beta)
+    } else {
+        worst
+    }
+}
+
+/// Search ran past its deadline
+struct OutOfTime;
+
+struct Search<'a> {
+    heuristics: &'a Heuristics,
+    deadline: Option<Instant>,
+}
+
+impl Search<'_> {
+    /// Best value of moves on the board
+    fn max_node<B: SearchBoard>(&self, board: &B, depth: u32, probability: f64) -> Result<f64, OutOfTime> {
+        if self.deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            return Err(OutOfTime);
+        }
+
+        let mut best = None;
+        for direction in DIRECTIONS {
+            if let Some(moved) = board.after_move(direction) {
+                let value = self.chance_node(&moved, depth, probability)?;
+                best = Some(best.map_or(value, |best: f64| best.max(value)));
+            }
+        }
+
+        Ok(best.unwrap_or_else(|| evaluate(board, self.heuristics) - LOSS_PENALTY))
+    }
+
+    /// Average value over every possible spawn after a move
+    fn chance_node<B: SearchBoard>(&self, board: &B, depth: u32, probability: f64) -> Result<f64, OutOfTime> {
+        let empty: Vec<usize> = board.empty_tiles().collect();
+        if depth <= 1 || empty.is_empty() || probability < MIN_PROBABILITY {
+            return Ok(evaluate(board, self.heuristics));
+        }
+
+        let tile_probability = probability / empty.len() as f64;
+        let mut total = 0.;
+        for index in empty.iter().copied() {
+            for (value, chance) in [(1, 1. - BIG_SPAWN_PROBABILITY), (2, BIG_SPAWN_PROBABILITY)] {
+                total += chance * self.max_node(&board.with_tile(index, value), depth - 1, tile_probability * chance)?;
+            }
+        }
+
+        Ok(total / empty.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exhaustive(depth: u32) -> AiConfig {
+        AiConfig { depth, time_budget: None, ..default() }
+    }
+
+    #[test]
+    fn evaluate_test() {
+        let heuristics = Heuristics::default();
+        let ordered = Board::from_values(4, 4, &[5, 4, 3, 2, 4, 3, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let scattered = Board::from_values(4, 4, &[2, 0, 4, 0, 0, 5, 0, 1, 3, 0, 2, 0, 0, 4, 0, 3]);
+
+        assert!(evaluate(&ordered, &heuristics) > evaluate(&scattered, &heuristics));
+    }
+
+    #[test]
+    fn only_legal_move_test() {
+        let values = Board::from_values(4, 4, &[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 0]);
+        let hint = search(&values, &exhaustive(2)).unwrap();
+
+        assert!(matches!(hint.direction, Direction::Right | Direction::Down));
+        assert_eq!(hint.depth, 2);
+        assert_eq!(search(&Board::from_values(3, 2, &[1, 2, 3, 3, 1, 2]), &exhaustive(3)), None);
+    }
+
+    #[test]
+    fn keeps_biggest_tile_in_corner_test() {
+        // Moving right or down would take 256 out of its corner
+        let values = Board::from_values(4, 4, &[8, 0, 6, 5, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let hint = search(&values, &exhaustive(1)).unwrap();
+
+        assert!(matches!(hint.direction, Direction::Left | Direction::Up), "{hint:?}");
+    }
+
+    #[test]
+    fn bitboard_search_agrees_with_board_test() {
+        let values = Board::from_values(4, 4, &[1, 1, 2, 0, 0, 3, 0, 1, 2, 0, 0, 0, 0, 0, 1, 4]);
+        let config = exhaustive(2);
+
+        assert_eq!(best_move(&Bitboard::from_board(&values).unwrap(), &config), best_move(&values, &config));
+    }
+
+    #[test]
+    fn worst_spawn_test() {
+        let heuristics = Heuristics::default();
+        // 4 on the last empty tile merges with the 4 above it, 2 leaves no move
+        let values = Board::from_values(2, 2, &[1, 2, 3, 0]);
+        assert_eq!(worst_spawn(&values, 1, &heuristics), Some((3, 1)));
+        assert_eq!(worst_spawn(&Board::from_values(2, 2, &[1, 2, 3, 4]), 2, &heuristics), None);
+
+        let values = Board::from_values(4, 4, &[6, 5, 4, 3, 5, 4, 3, 2, 1, 0, 0, 0, 0, 0, 0, 0]);
+        let bitboard = Bitboard::from_board(&values).unwrap();
+        let (index, value) = worst_spawn(&values, 2, &heuristics).unwrap();
+        assert_eq!(values[index], None);
+        assert_eq!(worst_spawn(&bitboard, 2, &heuristics), Some((index, value)));
+    }
+
+    #[test]
+    fn stepped_search_test() {
+        let values = Board::from_values(4, 4, &[1, 1, 2, 0, 0, 3, 0, 1, 2, 0, 0, 0, 0, 0, 1, 4]);
+        let config = exhaustive(3);
+        let mut stepped = SteppedSearch::new(&values, &config);
+
+        // Every step searches a single spawn at least
+        let mut steps = 1;
+        let result = loop {
+            match stepped.step(Duration::ZERO) {
+                Some(result) => break result,
+                None => steps += 1,
+            }
+        };
+        assert_eq!(result, search(&values, &config));
+        assert!(steps > 1);
+
+        let stuck = Board::from_values(3, 2, &[1, 2, 3, 3, 1, 2]);
+        assert_eq!(SteppedSearch::new(&stuck, &config).step(Duration::ZERO), Some(None));
+    }
+
+    #[test]
+    fn time_budget_test() {
+        let values = Board::from_values(8, 8, &[0; 64]).with_tile(0, 1).with_tile(63, 2);
+        let config = AiConfig { depth: 10, time_budget: Some(Duration::ZERO), ..default() };
+
+        // First depth is finished anyway
+        assert_eq!(best_move(&values, &config).unwrap().depth, 1);
+    }
+}
@@ -0,0 +1,254 @@
+use std::time::Duration;
+use bevy::prelude::*;
+use crate::game::ai::{AiConfig, SteppedSearch, FRAME_SEARCH_TIME};
+use crate::game::components::*;
+use crate::game::controls::KeyBindings;
+use crate::game::mode::GameMode;
+use crate::game::playback::playing_replay;
+use crate::game::states::*;
+use crate::game::systems::game_logic::{rules_setup, values_board};
+use crate::menu::AppState;
+
+/// Pause before the bot starts over after the board is stuck, in seconds
+const RESTART_DELAY: f32 = 3.;
+/// Step of delay change from the keyboard, in seconds
+const DELAY_STEP: f32 = 0.1;
+const MAX_DELAY: f32 = 2.;
+
+/// How the bot plays, kept between games
+#[derive(Resource, Copy, Clone, PartialEq, Debug)]
+pub struct AutoplaySettings {
+    /// Pause before every move, in seconds
+    pub delay: f32,
+    /// Moves without animation or delay
+    pub turbo: bool,
+    pub ai: AiConfig,
+}
+
+impl Default for AutoplaySettings {
+    fn default() -> Self {
+        Self {
+            delay: 0.3,
+            turbo: false,
+            // Quicker than hints, bot has to keep up in turbo
+            ai: AiConfig {
+                depth: 2,
+                time_budget: Some(Duration::from_millis(100)),
+                ..default()
+            },
+        }
+    }
+}
+
+/// State of the bot, exists only in games of [GameMode::Autoplay]
+#[derive(Resource)]
+pub struct Autoplay {
+    /// Bot is in control, human plays otherwise
+    pub active: bool,
+    pub paused: bool,
+    /// Single move requested while paused
+    step: bool,
+    /// Time waited before the next move or restart, in seconds
+    waited: f32,
+    /// Search for the next move, going on a bit every frame
+    search: Option<SteppedSearch>,
+    /// Move time to go back to after turbo
+    base_move_time: f32,
+}
+
+/// Marker for text with state of the bot and its keys
+#[derive(Component)]
+pub struct AutoplayStatusText;
+
+pub fn autoplay_plugin(app: &mut App) {
+    app.init_resource::<AutoplaySettings>()
+        .add_systems(
+            OnEnter(AppState::Game),
+            autoplay_setup
+                .after(rules_setup)
+                .run_if(autoplay_game.and(not(playing_replay))),
+        )
+        .add_systems(
+            Update,
+            (
+                autoplay_controls_system.in_set(InputSet),
+                autoplay_system.after(InputSet).run_if(in_state(GameState::Wait)),
+                autoplay_restart_system.run_if(in_state(GameState::Lose)),
+                update_autoplay_text.run_if(
+                    resource_exists_and_changed::<Autoplay>.or(resource_changed::<AutoplaySettings>),
+                ),
+            )
+                .chain()
+                .run_if(resource_exists::<Autoplay>)
+                .in_set(GameSet),
+        )
+        .add_systems(OnExit(AppState::Game), autoplay_cleanup.run_if(resource_exists::<Autoplay>));
+}
+
+/// Run condition for games played by the bot, not counted as player's games
+pub fn autoplay_game(game_mode: Res<GameMode>) -> bool {
+    *game_mode == GameMode::Autoplay
+}
+
+fn autoplay_setup(
+    mut commands: Commands,
+    settings: Res<AutoplaySettings>,
+    mut game_params: ResMut<GameParams>,
+) {
+    let base_move_time = game_params.move_time;
+    if settings.turbo {
+        game_params.move_time = 0.;
+    }
+    commands.insert_resource(Autoplay {
+        active: true,
+        paused: false,
+        step: false,
+        waited: 0.,
+        search: None,
+        base_move_time,
+    });
+
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 25.,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            right: Val::Px(20.),
+            ..default()
+        },
+        AutoplayStatusText,
+        OnGameScreen,
+    ));
+}
+
+fn autoplay_cleanup(
+    mut commands: Commands,
+    autoplay: Res<Autoplay>,
+    mut game_params: ResMut<GameParams>,
+) {
+    game_params.move_time = autoplay.base_move_time;
+    commands.remove_resource::<Autoplay>();
+}
+
+/// Keys of the bot: B gives control to the bot and takes it back, P pauses, N steps while paused,
+/// T switches turbo, minus and equals change the delay. Move keys take control back as well.
+fn autoplay_controls_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut settings: ResMut<AutoplaySettings>,
+    mut autoplay: ResMut<Autoplay>,
+    mut game_params: ResMut<GameParams>,
+) {
+    let human_moved = key_bindings.pressed_direction(&keyboard_input).is_some();
+    if human_moved && autoplay.active {
+        autoplay.active = false;
+    } else if keyboard_input.just_pressed(KeyCode::KeyB) {
+        autoplay.active = !autoplay.active;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        autoplay.paused = !autoplay.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyN) && autoplay.paused {
+        autoplay.step = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        settings.turbo = !settings.turbo;
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        settings.delay = (settings.delay - DELAY_STEP).max(0.);
+    } else if keyboard_input.just_pressed(KeyCode::Equal) {
+        settings.delay = (settings.delay + DELAY_STEP).min(MAX_DELAY);
+    }
+
+    // Search for a board the bot won't play on anymore is dropped
+    if !autoplay.active || (autoplay.paused && !autoplay.step) {
+        autoplay.bypass_change_detection().search = None;
+    }
+
+    let move_time = if autoplay.active && settings.turbo { 0. } else { autoplay.base_move_time };
+    if game_params.move_time != move_time {
+        game_params.move_time = move_time;
+    }
+}
+
+/// Chooses the next move every time the game waits for one
+fn autoplay_system(
+    time: Res<Time>,
+    settings: Res<AutoplaySettings>,
+    board_state_resource: Res<BoardStateResource>,
+    mut autoplay: ResMut<Autoplay>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut direction_messages: MessageWriter<DirectionMessage>,
+    block_query: Query<&Value, With<Block>>,
+) {
+    // Human moved in this frame
+    if !autoplay.active || matches!(*game_state, NextState::Pending(_)) {
+        return;
+    }
+
+    if let Some(search) = autoplay.bypass_change_detection().search.as_mut() {
+        let Some(result) = search.step(FRAME_SEARCH_TIME) else {
+            return;
+        };
+        autoplay.search = None;
+        autoplay.step = false;
+        autoplay.waited = 0.;
+        if let Some(hint) = result {
+            direction_messages.write(DirectionMessage(hint.direction));
+            game_state.set(GameState::Process);
+        }
+        return;
+    }
+
+    if autoplay.paused && !autoplay.step {
+        return;
+    }
+    let delay = if settings.turbo { 0. } else { settings.delay };
+    autoplay.bypass_change_detection().waited += time.delta_secs();
+    if autoplay.waited < delay {
+        return;
+    }
+
+    let values = values_board(&board_state_resource.0, &block_query);
+    autoplay.search = Some(SteppedSearch::new(&values, &settings.ai));
+}
+
+/// Starts a new game a bit after the bot lost
+fn autoplay_restart_system(
+    time: Res<Time>,
+    mut autoplay: ResMut<Autoplay>,
+    mut new_game_messages: MessageWriter<NewGameMessage>,
+) {
+    if !autoplay.active || autoplay.paused {
+        return;
+    }
+
+    autoplay.bypass_change_detection().waited += time.delta_secs();
+    if autoplay.waited >= RESTART_DELAY {
+        autoplay.waited = 0.;
+        new_game_messages.write(NewGameMessage);
+    }
+}
+
+fn update_autoplay_text(
+    autoplay: Res<Autoplay>,
+    settings: Res<AutoplaySettings>,
+    mut text_query: Query<&mut Text, With<AutoplayStatusText>>,
+) {
+    let state = match (autoplay.active, autoplay.paused) {
+        (false, _) => "you play".to_string(),
+        (true, true) => "bot paused".to_string(),
+        (true, false) if settings.turbo => "bot in turbo".to_string(),
+        (true, false) => format!("bot, {:.1}s per move", settings.delay),
+    };
+
+    for mut text in &mut text_query {
+        text.0 = format!("Autoplay: {state}\nB bot  P pause  N step  T turbo  -/+ delay");
+    }
+}
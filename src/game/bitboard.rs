@@ -0,0 +1,295 @@
+use std::sync::OnceLock;
+use crate::game::components::{Board, Direction};
+use crate::game::engine::DIRECTIONS;
+
+/// Biggest value a tile can have in [Bitboard], as power of two (16384), so merging two of them still fits
+pub const MAX_BITBOARD_VALUE: usize = 14;
+const ROWS: usize = 1 << 16;
+
+/// Results of moving every possible row towards column 0 and towards column 3
+struct RowTables {
+    left: Vec<u16>,
+    right: Vec<u16>,
+    left_score: Vec<u32>,
+    right_score: Vec<u32>,
+}
+
+static ROW_TABLES: OnceLock<RowTables> = OnceLock::new();
+
+fn row_tables() -> &'static RowTables {
+    ROW_TABLES.get_or_init(|| {
+        let mut tables = RowTables {
+            left: vec![0; ROWS],
+            right: vec![0; ROWS],
+            left_score: vec![0; ROWS],
+            right_score: vec![0; ROWS],
+        };
+
+        for row in 0..ROWS {
+            let (moved, score) = slide_row_left(row as u16);
+            tables.left[row] = moved;
+            tables.left_score[row] = score;
+        }
+        // Moving right is moving the mirrored row left
+        for row in 0..ROWS {
+            let mirrored = reverse_row(row as u16) as usize;
+            tables.right[row] = reverse_row(tables.left[mirrored]);
+            tables.right_score[row] = tables.left_score[mirrored];
+        }
+
+        tables
+    })
+}
+
+fn reverse_row(row: u16) -> u16 {
+    (row >> 12) | ((row >> 4) & 0x00F0) | ((row << 4) & 0x0F00) | (row << 12)
+}
+
+/// Slides the row towards column 0, 15s don't merge as the result doesn't fit into 4 bits
+fn slide_row_left(row: u16) -> (u16, u32) {
+    let mut tiles = [0u16; 4];
+    let mut next = 0;
+    let mut mergeable = false;
+    let mut score = 0;
+
+    for col in 0..4 {
+        let value = (row >> (4 * col)) & 0xF;
+        if value == 0 {
+            continue;
+        }
+
+        if mergeable && tiles[next - 1] == value && value < 0xF {
+            tiles[next - 1] += 1;
+            score += 1u32 << (value + 1);
+            mergeable = false;
+        } else {
+            tiles[next] = value;
+            next += 1;
+            mergeable = true;
+        }
+    }
+
+    let moved = tiles.iter().enumerate().fold(0, |moved, (col, &value)| moved | (value << (4 * col)));
+    (moved, score)
+}
+
+/// 4x4 board packed into 64 bits, 4 bits for every value as power of two
+///
+/// Row `r` takes bits `16 * r..16 * (r + 1)`, column `c` in it takes bits `4 * c..4 * (c + 1)`, 0 is empty.
+/// Moves are table lookups for every row, up and down moves transpose the board first.
+/// Fast enough for search, nothing is allocated on a move.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    /// Packs values of 4x4 board, `None` for other sizes or values above [MAX_BITBOARD_VALUE]
+    pub fn from_board(board: &Board<usize>) -> Option<Self> {
+        if board.width != 4 || board.height != 4 {
+            return None;
+        }
+
+        board.iter().enumerate().try_fold(Bitboard(0), |bitboard, (index, value)| match value {
+            Some(value) if *value > MAX_BITBOARD_VALUE => None,
+            Some(value) => Some(bitboard.with_value(index, *value)),
+            None => Some(bitboard),
+        })
+    }
+
+    pub fn to_board(self) -> Board<usize> {
+        Board::from_cells(4, 4, (0..16).map(|index| Some(self.value(index)).filter(|&value| value > 0)).collect())
+    }
+
+    /// Value at `col + row * 4`, 0 when empty
+    pub fn value(self, index: usize) -> usize {
+        ((self.0 >> (4 * index)) & 0xF) as usize
+    }
+
+    pub fn with_value(self, index: usize, value: usize) -> Self {
+        let shift = 4 * index;
+        Bitboard((self.0 & !(0xF << shift)) | ((value as u64 & 0xF) << shift))
+    }
+
+    fn row(self, row: usize) -> u16 {
+        (self.0 >> (16 * row)) as u16
+    }
+
+    /// Rows become columns
+    pub fn transpose(self) -> Self {
+        let x = self.0;
+        let a1 = x & 0xF0F0_0F0F_F0F0_0F0F;
+        let a2 = x & 0x0000_F0F0_0000_F0F0;
+        let a3 = x & 0x0F0F_0000_0F0F_0000;
+        let a = a1 | (a2 << 12) | (a3 >> 12);
+        let b1 = a & 0xFF00_FF00_00FF_00FF;
+        let b2 = a & 0x00FF_00FF_0000_0000;
+        let b3 = a & 0x0000_0000_FF00_FF00;
+
+        Bitboard(b1 | (b2 >> 24) | (b3 << 24))
+    }
+
+    fn move_rows(self, table: &[u16], score_table: &[u32]) -> (Self, u32) {
+        (0..4).fold((Bitboard(0), 0), |(moved, score), row| {
+            let index = self.row(row) as usize;
+            (Bitboard(moved.0 | (table[index] as u64) << (16 * row)), score + score_table[index])
+        })
+    }
+
+    /// Board after the move with score of its merges, same as [Board::apply_move] for values up to [MAX_BITBOARD_VALUE]
+    pub fn apply_move(self, direction: Direction) -> (Self, u32) {
+        let tables = row_tables();
+
+        match direction {
+            Direction::Left => self.move_rows(&tables.left, &tables.left_score),
+            Direction::Right => self.move_rows(&tables.right, &tables.right_score),
+            Direction::Up => {
+                let (moved, score) = self.transpose().move_rows(&tables.left, &tables.left_score);
+                (moved.transpose(), score)
+            }
+            Direction::Down => {
+                let (moved, score) = self.transpose().move_rows(&tables.right, &tables.right_score);
+                (moved.transpose(), score)
+            }
+        }
+    }
+
+    /// Directions that change the board
+    pub fn legal_moves(self) -> impl Iterator<Item = Direction> {
+        DIRECTIONS.into_iter().filter(move |&direction| self.apply_move(direction).0 != self)
+    }
+
+    pub fn is_terminal(self) -> bool {
+        self.legal_moves().next().is_none()
+    }
+
+    /// Indices of empty tiles
+    pub fn empty_indices(self) -> impl Iterator<Item = usize> {
+        (0..16).filter(move |&index| self.value(index) == 0)
+    }
+
+    pub fn empty_count(self) -> usize {
+        self.empty_indices().count()
+    }
+
+    pub fn max_value(self) -> usize {
+        (0..16).map(|index| self.value(index)).max().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    /// Row as 4x1 board of the engine
+    fn row_board(row: u16) -> Board<usize> {
+        Board::from_cells(
+            4,
+            1,
+            (0..4).map(|col| Some(((row >> (4 * col)) & 0xF) as usize).filter(|&value| value > 0)).collect(),
+        )
+    }
+
+    fn row_from_board(board: &Board<usize>) -> u16 {
+        board
+            .iter()
+            .enumerate()
+            .fold(0, |row, (col, value)| row | ((value.unwrap_or(0) as u16) << (4 * col)))
+    }
+
+    #[test]
+    fn row_tables_agree_with_engine_test() {
+        let tables = row_tables();
+
+        // Every row a bitboard takes
+        let rows = (0..=u16::MAX).filter(|&row| (0..4).all(|col| ((row >> (4 * col)) & 0xF) as usize <= MAX_BITBOARD_VALUE));
+        for row in rows {
+            for (direction, table, score_table) in [
+                (Direction::Left, &tables.left, &tables.left_score),
+                (Direction::Right, &tables.right, &tables.right_score),
+            ] {
+                let mut board = row_board(row);
+                let outcome = board.apply_move(direction);
+
+                assert_eq!(table[row as usize], row_from_board(&board), "row {row:04x} {direction:?}");
+                assert_eq!(score_table[row as usize] as u64, outcome.score, "row {row:04x} {direction:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn biggest_values_do_not_merge_test() {
+        let row = 0x00FF;
+        assert_eq!(slide_row_left(row), (row, 0));
+        assert_eq!(slide_row_left(0xEE00), (0x000F, 1 << 15));
+    }
+
+    #[test]
+    fn transpose_test() {
+        let bitboard = Bitboard(0xFEDC_BA98_7654_3210);
+        let transposed = bitboard.transpose();
+
+        for row in 0..4 {
+            for col in 0..4 {
+                assert_eq!(transposed.value(row + col * 4), bitboard.value(col + row * 4));
+            }
+        }
+        assert_eq!(transposed.transpose(), bitboard);
+    }
+
+    #[test]
+    fn board_round_trip_test() {
+        let mut board = Board::new(4, 4);
+        board[0] = Some(1);
+        board[6] = Some(11);
+        board[15] = Some(14);
+
+        let bitboard = Bitboard::from_board(&board).unwrap();
+        assert_eq!(bitboard.to_board(), board);
+        assert_eq!(bitboard.empty_count(), 13);
+        assert_eq!(bitboard.max_value(), 14);
+
+        // Two of these would merge into a value that doesn't fit
+        board[1] = Some(15);
+        assert_eq!(Bitboard::from_board(&board), None);
+        assert_eq!(Bitboard::from_board(&Board::new(5, 4)), None);
+    }
+
+    #[test]
+    fn tile_access_test() {
+        let bitboard = Bitboard(0).with_value(0, 3).with_value(5, 15).with_value(15, 1);
+
+        assert_eq!(bitboard.value(0), 3);
+        assert_eq!(bitboard.value(5), 15);
+        assert_eq!(bitboard.value(15), 1);
+        assert_eq!(bitboard.with_value(5, 2).value(5), 2);
+        assert_eq!(bitboard.empty_indices().collect::<Vec<_>>(), [1, 2, 3, 4, 6, 7, 8, 9, 10, 11, 12, 13, 14]);
+        assert_eq!(bitboard.max_value(), 15);
+    }
+
+    #[test]
+    fn moves_agree_with_engine_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2048);
+
+        for _ in 0..10_000 {
+            let mut board = Board::new(4, 4);
+            for index in 0..16 {
+                if rng.random_ratio(2, 3) {
+                    board[index] = Some(rng.random_range(1..8));
+                }
+            }
+            let bitboard = Bitboard::from_board(&board).unwrap();
+
+            for direction in DIRECTIONS {
+                let mut moved = board.clone();
+                let outcome = moved.apply_move(direction);
+                let (moved_bitboard, score) = bitboard.apply_move(direction);
+
+                assert_eq!(moved_bitboard.to_board(), moved, "{direction:?} on {bitboard:x?}");
+                assert_eq!(score as u64, outcome.score);
+            }
+            assert_eq!(bitboard.legal_moves().collect::<Vec<_>>(), board.legal_moves());
+            assert_eq!(bitboard.is_terminal(), board.is_terminal());
+        }
+    }
+}
@@ -1,24 +1,67 @@
 use bevy::ecs::lifecycle::HookContext;
 use bevy::ecs::world::DeferredWorld;
 use bevy::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
 use std::fmt::{Display, Formatter};
-use crate::game::SIZE;
+use std::time::Duration;
+use crate::game::RECT_SIZE;
 
 /// Marker for game screen
 #[derive(Component)]
 pub struct OnGameScreen;
 
-/// Resource to hold current and previous boards
+/// Resource to hold current board
 #[derive(Resource)]
-pub struct BoardStateResource(pub Board<SIZE, Entity>);
+pub struct BoardStateResource(pub Board<Entity>);
 
-/// presumably vec of NxN map, with `col + row * N` as index
+/// Vec of `width`x`height` map, with `col + row * width` as index
 #[derive(Resource, Deref, DerefMut, Eq, PartialEq, Debug, Clone)]
-pub struct Board<const N: usize, T>(pub Vec<Option<T>>);
+pub struct Board<T> {
+    pub width: usize,
+    pub height: usize,
+    #[deref]
+    pub cells: Vec<Option<T>>,
+}
+
+impl<T: Clone> Board<T> {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![None; width * height],
+        }
+    }
+}
+
+impl<T> Board<T> {
+    pub fn from_cells(width: usize, height: usize, cells: Vec<Option<T>>) -> Self {
+        assert_eq!(cells.len(), width * height, "Board should be {width}x{height}");
+        Self { width, height, cells }
+    }
+
+    /// Indices of every row or column for blocks to slide along, ordered from the edge blocks slide to
+    pub fn lines(&self, direction: &Direction) -> Vec<Vec<usize>> {
+        let (width, height) = (self.width, self.height);
+
+        match direction {
+            Direction::Left => (0..height)
+                .map(|row| (0..width).map(|col| col + row * width).collect())
+                .collect(),
+            Direction::Right => (0..height)
+                .map(|row| (0..width).rev().map(|col| col + row * width).collect())
+                .collect(),
+            Direction::Up => (0..width)
+                .map(|col| (0..height).map(|row| col + row * width).collect())
+                .collect(),
+            Direction::Down => (0..width)
+                .map(|col| (0..height).rev().map(|row| col + row * width).collect())
+                .collect(),
+        }
+    }
 
-impl<const N: usize, T> Board<N, T> {
     pub fn empty_indices(&self) -> Vec<usize> {
-        self.0.iter()
+        self.iter()
             .enumerate()
             .filter(|(_, e)| e.is_none())
             .map(|(i, _)| i)
@@ -26,18 +69,78 @@ impl<const N: usize, T> Board<N, T> {
     }
 }
 
-impl Display for Board<SIZE, Entity> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let (chunks, []) = self.as_chunks::<SIZE>() else { panic!("Board should be SIZExSIZE") };
+#[cfg(test)]
+impl Board<usize> {
+    /// Board of tile values in row order, 0 for an empty cell
+    pub fn from_values(width: usize, height: usize, values: &[usize]) -> Self {
+        Self::from_cells(width, height, values.iter().map(|&value| Some(value).filter(|&value| value > 0)).collect())
+    }
+}
 
+impl Display for Board<Entity> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Board :")?;
-        for chunk in chunks {
+        for chunk in self.chunks(self.width) {
             writeln!(f, "      {:?}", chunk)?;
         }
         write!(f, "")
     }
 }
 
+/// Smallest board size that can be selected
+pub const MIN_BOARD_SIZE: usize = 3;
+/// Biggest board size that can be selected
+pub const MAX_BOARD_SIZE: usize = 8;
+/// Space on screen given to the board, tiles shrink to fit into it
+const BOARD_EXTENT: f32 = 1000.;
+/// Center of the board on screen
+const BOARD_CENTER: Vec2 = Vec2::new(-125., 0.);
+
+/// Size of the board for new games
+#[derive(Resource, Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BoardSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Default for BoardSize {
+    fn default() -> Self {
+        Self { width: 4, height: 4 }
+    }
+}
+
+/// Geometry of the board on screen, set up when game starts
+#[derive(Resource, Copy, Clone, PartialEq, Debug)]
+pub struct BoardLayout {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: f32,
+    pub center: Vec2,
+}
+
+impl BoardLayout {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            tile_size: (BOARD_EXTENT / width.max(height) as f32).min(RECT_SIZE),
+            center: BOARD_CENTER,
+        }
+    }
+
+    /// Scale to apply to elements made for [RECT_SIZE]
+    pub fn scale(&self) -> f32 {
+        self.tile_size / RECT_SIZE
+    }
+}
+
+impl Default for BoardLayout {
+    fn default() -> Self {
+        let BoardSize { width, height } = BoardSize::default();
+        Self::new(width, height)
+    }
+}
+
 /// Marker for movable element
 #[derive(Component)]
 pub struct Block;
@@ -60,11 +163,43 @@ pub struct Value(pub usize);
 fn on_value_insert(mut world: DeferredWorld, HookContext { entity, .. }: HookContext) {
     use crate::game::sprites::BoardSprites;
 
-    let new_value = world.get::<Value>(entity).expect("No value exists on block");
+    let new_value = world.get::<Value>(entity).expect("No value exists on block").0;
     let board_sprites = world.get_resource::<BoardSprites>().expect("No BoardSprites available");
 
-    let new_sprites = board_sprites.get(&new_value.0).expect("No sprite for value exists").clone();
-    world.commands().entity(entity).insert(new_sprites);
+    let new_sprites = board_sprites.for_value(new_value).expect("No sprite for value exists");
+    let old_labels: Vec<Entity> = world
+        .get::<Children>(entity)
+        .map(|children| children.iter().filter(|&child| world.get::<ValueLabel>(child).is_some()).collect())
+        .unwrap_or_default();
+
+    let mut commands = world.commands();
+    for label in old_labels {
+        commands.entity(label).despawn();
+    }
+
+    let mut entity_commands = commands.entity(entity);
+    entity_commands.insert(new_sprites);
+    if let Some(label) = value_label(new_value) {
+        entity_commands.with_child(label);
+    }
+}
+
+/// Face value written on top of the block, sprites only go up to 2048
+fn value_label(value: usize) -> Option<(Text2d, TextFont, TextColor, Transform, ValueLabel)> {
+    use crate::game::sprites::MAX_SPRITE_VALUE;
+
+    (value > MAX_SPRITE_VALUE).then(|| {
+        (
+            Text2d::new((1u64 << value).to_string()),
+            TextFont {
+                font_size: 64.,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            Transform::from_xyz(0., 0., 1.),
+            ValueLabel,
+        )
+    })
 }
 
 #[cfg(test)]
@@ -73,6 +208,10 @@ fn on_value_insert(world: DeferredWorld, HookContext { entity, .. }: HookContext
     println!("Inserted Value: {}", new_value.0);
 }
 
+/// Marker for text with face value of the [Block], for values without own sprite
+#[derive(Component)]
+pub struct ValueLabel;
+
 /// Move to be processed
 /// 1. move to
 /// 1. in time
@@ -81,7 +220,7 @@ fn on_value_insert(world: DeferredWorld, HookContext { entity, .. }: HookContext
 pub struct QueuedMove(pub Position, pub Timer, pub Option<Entity>);
 
 /// Direction
-#[derive(Default, Eq, PartialEq, Debug)]
+#[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Direction {
     Left,
     #[default]
@@ -90,32 +229,6 @@ pub enum Direction {
     Down
 }
 
-/// Direction of rotation
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
-pub enum RotateBy {
-    None, Left, Right, Full
-}
-
-impl RotateBy {
-    pub fn from_direction(direction: &Direction) -> Self {
-        match direction {
-            Direction::Left => RotateBy::None,
-            Direction::Right => RotateBy::Full,
-            Direction::Up => RotateBy::Left,
-            Direction::Down => RotateBy::Right,
-        }
-    }
-
-    pub fn revert(&self) -> Self {
-        match self {
-            RotateBy::None => RotateBy::None,
-            RotateBy::Left => RotateBy::Right,
-            RotateBy::Right => RotateBy::Left,
-            RotateBy::Full => RotateBy::Full,
-        }
-    }
-}
-
 /// Message for movement to new position in global coordinates
 /// 1. entity to move
 /// 1. position component to move to
@@ -138,6 +251,26 @@ pub struct CollisionMessage{
 #[derive(Message, Default)]
 pub struct DirectionMessage(pub Direction);
 
+/// New tile placed on the board, `value` is power of two like [Value]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TileSpawn {
+    pub col: usize,
+    pub row: usize,
+    pub value: usize,
+}
+
+/// Spawns to use instead of the spawner's after the next move, set by replay playback
+#[derive(Resource, Default)]
+pub struct ForcedSpawn(pub Option<Vec<TileSpawn>>);
+
+/// Move from touch, mouse or gamepad, made at once while waiting for input and buffered otherwise
+#[derive(Message, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct MoveInputMessage(pub Direction);
+
+/// Message for a tile spawned after a move
+#[derive(Message, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SpawnMessage(pub TileSpawn);
+
 /// Message for displaying merge effect
 #[derive(Message)]
 pub struct MergeEffectMessage {
@@ -145,8 +278,138 @@ pub struct MergeEffectMessage {
     pub line: (Vec2, Vec2)
 }
 
+/// Message for merge that happened during movement, with new value of the merged block
+#[derive(Message)]
+pub struct MergeMessage(pub usize);
+
+/// Score of the current game, sum of face values produced by merges
+#[derive(Resource, Default, Deref, DerefMut, Eq, PartialEq, Debug)]
+pub struct Score(pub u64);
+
+/// Message to start a new game without leaving the game screen
+#[derive(Message)]
+pub struct NewGameMessage;
+
+/// Moves made in the current game
+#[derive(Resource, Default, Deref, DerefMut, Eq, PartialEq, Debug)]
+pub struct Moves(pub u64);
+
+/// Time spent in the current game, not counting the time after it ended
+#[derive(Resource, Default, Deref, DerefMut, Eq, PartialEq, Debug)]
+pub struct PlayTime(pub Duration);
+
+/// Seed of the current game, same seed and same moves give the same game
+#[derive(Resource, Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct GameSeed(pub u64);
+
+impl GameSeed {
+    /// New seed from OS entropy
+    pub fn random() -> Self {
+        let mut bytes = [0u8; 8];
+        getrandom::fill(&mut bytes).expect("failed to fill seed");
+        Self(u64::from_le_bytes(bytes))
+    }
+}
+
+/// Seed for new games, random seed for every game when `None`
+#[derive(Resource, Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SeedSetting(pub Option<u64>);
+
+/// Marker for text that displays [Score]
+#[derive(Component)]
+pub struct ScoreText;
+
+/// Marker for text that displays best score
+#[derive(Component)]
+pub struct BestScoreText;
+
+/// Marker for text that displays [GameSeed]
+#[derive(Component)]
+pub struct SeedText;
+
+/// Values of the board at rest, enough to restore the game to that moment
+#[derive(Clone)]
+pub struct BoardSnapshot {
+    pub values: Board<usize>,
+    pub score: u64,
+    pub moves: u64,
+    pub rng: ChaCha8Rng,
+}
+
+/// Undo and redo stacks of [BoardSnapshot]
+#[derive(Resource, Default)]
+pub struct History {
+    /// Snapshot of the board as it is now
+    pub current: Option<BoardSnapshot>,
+    pub undo: VecDeque<BoardSnapshot>,
+    pub redo: Vec<BoardSnapshot>,
+}
+
+/// Message to move through [History]
+#[derive(Message, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum HistoryMessage {
+    Undo,
+    Redo,
+}
+
+/// Message for undo that took the game back from the loss, the loss doesn't count anymore
+#[derive(Message)]
+pub struct LossUndoneMessage;
+
+/// Actions of buttons on game screen
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum GameButtonAction {
+    Undo,
+    Redo,
+}
+
+/// Actions of buttons on the win and lose overlays
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum OverlayButtonAction {
+    KeepGoing,
+    Undo,
+    NewGame,
+    MainMenu,
+    #[cfg(not(target_arch = "wasm32"))]
+    Quit,
+}
+
 /// Params for changeable game params
 #[derive(Resource)]
 pub struct GameParams {
     pub move_time: f32,
-}
\ No newline at end of file
+}
+
+/// Biggest [InputBuffer] that can be set
+pub const MAX_INPUT_BUFFER: usize = 3;
+
+/// How many moves pressed while tiles are moving are kept, 0 drops them
+#[derive(Resource, Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct InputBuffer(pub usize);
+
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self(2)
+    }
+}
+
+/// Move pressed while tiles are moving finishes their movement at once
+#[derive(Resource, Component, Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SnapMoves(pub bool);
+
+/// Moves pressed while tiles are moving, played one per wait for input
+#[derive(Resource, Default)]
+pub struct InputQueue(pub VecDeque<Direction>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_label_test() {
+        assert!(value_label(11).is_none());
+
+        let (text, ..) = value_label(12).unwrap();
+        assert_eq!(text.0, "4096");
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::game::components::Direction;
+use crate::menu::MenuState;
+use crate::storage::StorageResource;
+
+const SETTINGS_KEY: &str = "settings.ron";
+/// Version of [SettingsFile], files with other version are ignored
+const SETTINGS_VERSION: u32 = 1;
+
+/// Keys taken by the bot in autoplay games, see [crate::game::autoplay]
+const RESERVED_KEYS: [KeyCode; 6] = [KeyCode::KeyB, KeyCode::KeyP, KeyCode::KeyN, KeyCode::KeyT, KeyCode::Minus, KeyCode::Equal];
+
+/// What a key does in game
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Undo,
+    Redo,
+    Restart,
+    Hint,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Undo,
+        Action::Redo,
+        Action::Restart,
+        Action::Hint,
+        Action::Pause,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "Up",
+            Action::MoveDown => "Down",
+            Action::MoveLeft => "Left",
+            Action::MoveRight => "Right",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Restart => "Restart",
+            Action::Hint => "Hint",
+            Action::Pause => "Pause",
+        }
+    }
+
+    /// Direction of the move actions
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            Action::MoveUp => Some(Direction::Up),
+            Action::MoveDown => Some(Direction::Down),
+            Action::MoveLeft => Some(Direction::Left),
+            Action::MoveRight => Some(Direction::Right),
+            _ => None,
+        }
+    }
+}
+
+/// Ready made sets of bindings, differing in the keys for moves
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ControlsPreset {
+    Arrows,
+    Wasd,
+    Vim,
+}
+
+impl ControlsPreset {
+    pub const ALL: [ControlsPreset; 3] = [ControlsPreset::Arrows, ControlsPreset::Wasd, ControlsPreset::Vim];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControlsPreset::Arrows => "Arrows",
+            ControlsPreset::Wasd => "WASD",
+            ControlsPreset::Vim => "Vim",
+        }
+    }
+}
+
+/// Why a key can't be bound to an action
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BindingConflict {
+    /// Key is bound to another action
+    Action(Action),
+    /// Key is taken by the bot
+    Reserved,
+}
+
+/// Key of every action, one key per action and one action per key
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct KeyBindings(BTreeMap<Action, KeyCode>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self::preset(ControlsPreset::Arrows)
+    }
+}
+
+impl KeyBindings {
+    pub fn preset(preset: ControlsPreset) -> Self {
+        let moves = match preset {
+            ControlsPreset::Arrows => [KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight],
+            ControlsPreset::Wasd => [KeyCode::KeyW, KeyCode::KeyS, KeyCode::KeyA, KeyCode::KeyD],
+            ControlsPreset::Vim => [KeyCode::KeyK, KeyCode::KeyJ, KeyCode::KeyH, KeyCode::KeyL],
+        };
+        // H moves left in vim, hint goes to the key of searching instead
+        let hint = if preset == ControlsPreset::Vim { KeyCode::Slash } else { KeyCode::KeyH };
+        let (undo, redo) = if preset == ControlsPreset::Vim { (KeyCode::KeyU, KeyCode::KeyY) } else { (KeyCode::KeyZ, KeyCode::KeyY) };
+
+        Self(BTreeMap::from([
+            (Action::MoveUp, moves[0]),
+            (Action::MoveDown, moves[1]),
+            (Action::MoveLeft, moves[2]),
+            (Action::MoveRight, moves[3]),
+            (Action::Undo, undo),
+            (Action::Redo, redo),
+            (Action::Restart, KeyCode::KeyR),
+            (Action::Hint, hint),
+            (Action::Pause, KeyCode::Escape),
+        ]))
+    }
+
+    pub fn key(&self, action: Action) -> Option<KeyCode> {
+        self.0.get(&action).copied()
+    }
+
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.0.iter().find(|(_, bound)| **bound == key).map(|(action, _)| *action)
+    }
+
+    pub fn just_pressed(&self, action: Action, keyboard_input: &ButtonInput<KeyCode>) -> bool {
+        self.key(action).is_some_and(|key| keyboard_input.just_pressed(key))
+    }
+
+    /// Direction of the move key pressed in this frame, the first one when several are
+    pub fn pressed_direction(&self, keyboard_input: &ButtonInput<KeyCode>) -> Option<Direction> {
+        [Action::MoveLeft, Action::MoveRight, Action::MoveDown, Action::MoveUp]
+            .into_iter()
+            .find(|action| self.just_pressed(*action, keyboard_input))
+            .and_then(Action::direction)
+    }
+
+    /// Binds `key` to `action` in place of its old key, unless the key is taken
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Result<(), BindingConflict> {
+        if RESERVED_KEYS.contains(&key) {
+            return Err(BindingConflict::Reserved);
+        }
+        match self.action(key) {
+            Some(other) if other != action => Err(BindingConflict::Action(other)),
+            _ => {
+                self.0.insert(action, key);
+                Ok(())
+            }
+        }
+    }
+
+    /// Bindings rebuilt through [KeyBindings::bind], so edited files can't bring conflicts in
+    ///
+    /// Loaded keys go first, actions left without a key get their default key, if it's still free.
+    fn validated(&self) -> Self {
+        let mut bindings = Self(BTreeMap::new());
+        for action in Action::ALL {
+            if let Some(key) = self.key(action) {
+                if let Err(conflict) = bindings.bind(action, key) {
+                    warn!("Key {:?} of {:?} conflicts with {:?}, using default", key, action, conflict);
+                }
+            }
+        }
+
+        let defaults = Self::default();
+        for action in Action::ALL {
+            if let (None, Some(key)) = (bindings.key(action), defaults.key(action)) {
+                if bindings.bind(action, key).is_err() {
+                    warn!("Default key {:?} of {:?} is taken, action has no key", key, action);
+                }
+            }
+        }
+        bindings
+    }
+}
+
+/// Short name of a key for the screen, "W" instead of "KeyW"
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    match name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")) {
+        Some(short) => short.to_string(),
+        None => name,
+    }
+}
+
+/// Layout of settings file in storage
+#[derive(Serialize, Deserialize)]
+struct SettingsFile {
+    version: u32,
+    key_bindings: KeyBindings,
+}
+
+pub fn controls_plugin(app: &mut App) {
+    app.init_resource::<KeyBindings>()
+        .add_systems(Startup, load_settings)
+        .add_systems(OnExit(MenuState::SettingsControls), save_settings);
+}
+
+fn load_settings(
+    storage: Res<StorageResource>,
+    mut key_bindings: ResMut<KeyBindings>,
+) {
+    let Some(contents) = storage.read(SETTINGS_KEY) else {
+        debug!("No settings stored yet");
+        return;
+    };
+
+    match ron::from_str::<SettingsFile>(&contents) {
+        Ok(file) if file.version == SETTINGS_VERSION => {
+            *key_bindings = file.key_bindings.validated();
+        }
+        Ok(file) => warn!("Settings version {} is not supported, using defaults", file.version),
+        Err(e) => warn!("Settings can't be read, using defaults: {}", e),
+    }
+}
+
+fn save_settings(
+    storage: Res<StorageResource>,
+    key_bindings: Res<KeyBindings>,
+) {
+    let file = SettingsFile {
+        version: SETTINGS_VERSION,
+        key_bindings: key_bindings.clone(),
+    };
+
+    let contents = match ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warn!("Settings can't be serialized: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = storage.write(SETTINGS_KEY, &contents) {
+        warn!("Settings can't be saved: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_have_no_conflicts_test() {
+        for preset in ControlsPreset::ALL {
+            let bindings = KeyBindings::preset(preset);
+            for action in Action::ALL {
+                let key = bindings.key(action).unwrap();
+                assert_eq!(bindings.action(key), Some(action), "{preset:?}");
+                assert!(!RESERVED_KEYS.contains(&key), "{preset:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn bind_test() {
+        let mut bindings = KeyBindings::default();
+
+        assert_eq!(bindings.bind(Action::MoveUp, KeyCode::KeyZ), Err(BindingConflict::Action(Action::Undo)));
+        assert_eq!(bindings.bind(Action::MoveUp, KeyCode::KeyB), Err(BindingConflict::Reserved));
+        assert_eq!(bindings.key(Action::MoveUp), Some(KeyCode::ArrowUp));
+
+        assert_eq!(bindings.bind(Action::MoveUp, KeyCode::KeyW), Ok(()));
+        assert_eq!(bindings.bind(Action::MoveUp, KeyCode::KeyW), Ok(()));
+        assert_eq!(bindings.action(KeyCode::KeyW), Some(Action::MoveUp));
+        assert_eq!(bindings.action(KeyCode::ArrowUp), None);
+
+        let mut keyboard_input = ButtonInput::default();
+        keyboard_input.press(KeyCode::KeyW);
+        assert_eq!(bindings.pressed_direction(&keyboard_input), Some(Direction::Up));
+    }
+
+    #[test]
+    fn validated_test() {
+        let mut loaded = KeyBindings::preset(ControlsPreset::Wasd);
+        // Edited file with a key twice, a key of the bot and an action left out
+        loaded.0.insert(Action::Restart, KeyCode::KeyW);
+        loaded.0.insert(Action::Hint, KeyCode::KeyB);
+        loaded.0.remove(&Action::Pause);
+
+        let bindings = loaded.validated();
+        assert_eq!(bindings.key(Action::MoveUp), Some(KeyCode::KeyW));
+        assert_eq!(bindings.key(Action::MoveLeft), Some(KeyCode::KeyA));
+        assert_eq!(bindings.key(Action::Restart), Some(KeyCode::KeyR));
+        assert_eq!(bindings.key(Action::Hint), Some(KeyCode::KeyH));
+        assert_eq!(bindings.key(Action::Pause), Some(KeyCode::Escape));
+        for action in Action::ALL {
+            let key = bindings.key(action).unwrap();
+            assert_eq!(bindings.action(key), Some(action));
+        }
+
+        assert_eq!(KeyBindings::preset(ControlsPreset::Vim).validated(), KeyBindings::preset(ControlsPreset::Vim));
+    }
+
+    #[test]
+    fn settings_file_test() {
+        let file = SettingsFile { version: SETTINGS_VERSION, key_bindings: KeyBindings::preset(ControlsPreset::Vim) };
+        let contents = ron::to_string(&file).unwrap();
+        let loaded: SettingsFile = ron::from_str(&contents).unwrap();
+
+        assert_eq!(loaded.key_bindings, KeyBindings::preset(ControlsPreset::Vim));
+    }
+}
@@ -0,0 +1,241 @@
+use rand::Rng;
+use crate::game::components::{Board, Direction, TileSpawn};
+
+/// Every direction, in the order [Board::legal_moves] lists them
+pub const DIRECTIONS: [Direction; 4] = [Direction::Left, Direction::Right, Direction::Up, Direction::Down];
+
+/// Tile sliding in a move, indices are `col + row * width` like [Board]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TileMove {
+    pub from: usize,
+    pub to: usize,
+    /// Index, before the move, of the tile this one merges into at `to`
+    pub merged_with: Option<usize>,
+}
+
+/// Tile produced by a merge
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TileMerge {
+    /// Index of the merged tile after the move
+    pub index: usize,
+    /// New value, power of two
+    pub value: usize,
+}
+
+/// What happened to the board in a move
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct MoveOutcome {
+    /// Every tile on the board, in order of lines, tiles that stay in place have `from == to`
+    pub moves: Vec<TileMove>,
+    pub merges: Vec<TileMerge>,
+    /// Face values of merged tiles added together
+    pub score: u64,
+    /// Move that doesn't change the board isn't a legal move
+    pub changed: bool,
+}
+
+/// Game rules on a board of values, as powers of two, without anything from ECS
+impl Board<usize> {
+    /// Slides and merges tiles towards `direction`, every tile merges at most once per move
+    pub fn apply_move(&mut self, direction: Direction) -> MoveOutcome {
+        let mut outcome = MoveOutcome::default();
+        let previous = self.clone();
+
+        for line in previous.lines(&direction) {
+            // Position in line and value of the last placed tile, if it can still be merged into
+            let mut next = 0;
+            let mut mergeable: Option<(usize, usize)> = None;
+
+            for (position, &from) in line.iter().enumerate() {
+                let Some(value) = previous[from] else {
+                    continue;
+                };
+                self[from] = None;
+
+                match mergeable {
+                    Some((target, target_value)) if target_value == value => {
+                        let to = line[next - 1];
+                        self[to] = Some(value + 1);
+                        outcome.moves.push(TileMove { from, to, merged_with: Some(line[target]) });
+                        outcome.merges.push(TileMerge { index: to, value: value + 1 });
+                        outcome.score += 1u64 << (value + 1);
+                        mergeable = None;
+                    }
+                    _ => {
+                        let to = line[next];
+                        self[to] = Some(value);
+                        outcome.moves.push(TileMove { from, to, merged_with: None });
+                        mergeable = Some((position, value));
+                        next += 1;
+                    }
+                }
+            }
+        }
+
+        outcome.changed = self.cells != previous.cells;
+        outcome
+    }
+
+    /// Whether a move towards `direction` changes the board
+    pub fn can_move(&self, direction: Direction) -> bool {
+        self.lines(&direction).iter().any(|line| {
+            line.windows(2).any(|pair| match (self[pair[0]], self[pair[1]]) {
+                // Tile can slide into empty tile before it
+                (None, Some(_)) => true,
+                (Some(first), Some(second)) => first == second,
+                _ => false,
+            })
+        })
+    }
+
+    /// Directions that change the board
+    pub fn legal_moves(&self) -> Vec<Direction> {
+        DIRECTIONS.into_iter().filter(|&direction| self.can_move(direction)).collect()
+    }
+
+    /// No move changes the board
+    pub fn is_terminal(&self) -> bool {
+        self.legal_moves().is_empty()
+    }
+
+    /// Places a random tile on an empty tile, `None` when the board is full
+    pub fn spawn_tile(&mut self, rng: &mut impl Rng) -> Option<TileSpawn> {
+        let spawn = random_spawn(self, rng)?;
+        let index = spawn.col + spawn.row * self.width;
+        self[index] = Some(spawn.value);
+
+        Some(spawn)
+    }
+}
+
+/// Random empty tile of any board, with value 4 in one of five spawns and 2 otherwise
+///
+/// Order of random draws is kept, so games with the same seed stay the same.
+pub fn random_spawn<T>(board: &Board<T>, rng: &mut impl Rng) -> Option<TileSpawn> {
+    let empty_spaces = board.empty_indices();
+    if empty_spaces.is_empty() {
+        return None;
+    }
+
+    let big_val: bool = rng.random_ratio(1, 5);
+    let index = empty_spaces[rng.random_range(0..empty_spaces.len())];
+
+    Some(TileSpawn {
+        col: index % board.width,
+        row: index / board.width,
+        value: if big_val { 2 } else { 1 },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn slide_and_merge_test() {
+        let mut values = Board::from_values(4, 4, &[1, 0, 1, 1, 2, 2, 2, 2, 2, 1, 1, 0, 0, 0, 0, 3]);
+        let outcome = values.apply_move(Direction::Left);
+
+        assert_eq!(values, Board::from_values(4, 4, &[2, 1, 0, 0, 3, 3, 0, 0, 2, 2, 0, 0, 3, 0, 0, 0]));
+        assert_eq!(outcome.score, 4 + 8 + 8 + 4);
+        assert!(outcome.changed);
+        assert_eq!(
+            outcome.merges,
+            vec![
+                TileMerge { index: 0, value: 2 },
+                TileMerge { index: 4, value: 3 },
+                TileMerge { index: 5, value: 3 },
+                TileMerge { index: 9, value: 2 },
+            ]
+        );
+        // First row, third tile merges into the first one, which stays in place
+        assert_eq!(&outcome.moves[..3], &[
+            TileMove { from: 0, to: 0, merged_with: None },
+            TileMove { from: 2, to: 0, merged_with: Some(0) },
+            TileMove { from: 3, to: 1, merged_with: None },
+        ]);
+    }
+
+    #[test]
+    fn merged_tile_does_not_merge_again_test() {
+        let mut values = Board::from_values(4, 1, &[1, 1, 2, 0]);
+        let outcome = values.apply_move(Direction::Left);
+
+        assert_eq!(values, Board::from_values(4, 1, &[2, 2, 0, 0]));
+        assert_eq!(outcome.score, 4);
+    }
+
+    #[test]
+    fn directions_test() {
+        let start = Board::from_values(3, 2, &[1, 0, 2, 0, 1, 2]);
+
+        let mut right = start.clone();
+        right.apply_move(Direction::Right);
+        assert_eq!(right, Board::from_values(3, 2, &[0, 1, 2, 0, 1, 2]));
+
+        let mut up = start.clone();
+        let outcome = up.apply_move(Direction::Up);
+        assert_eq!(up, Board::from_values(3, 2, &[1, 1, 3, 0, 0, 0]));
+        assert_eq!(outcome.score, 8);
+
+        let mut down = start.clone();
+        down.apply_move(Direction::Down);
+        assert_eq!(down, Board::from_values(3, 2, &[0, 0, 0, 1, 1, 3]));
+    }
+
+    #[test]
+    fn unchanged_move_test() {
+        let mut values = Board::from_values(4, 4, &[1, 2, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let outcome = values.apply_move(Direction::Left);
+
+        assert!(!outcome.changed);
+        assert_eq!(outcome.score, 0);
+        assert!(outcome.moves.iter().all(|tile_move| tile_move.from == tile_move.to));
+        assert_eq!(values.legal_moves(), vec![Direction::Right, Direction::Down]);
+    }
+
+    #[test]
+    fn legal_moves_agree_with_apply_move_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2048);
+        for _ in 0..200 {
+            let mut values = Board::new(4, 4);
+            for _ in 0..rng.random_range(1..16) {
+                values.spawn_tile(&mut rng);
+            }
+
+            for direction in DIRECTIONS {
+                let mut moved = values.clone();
+                assert_eq!(moved.apply_move(direction).changed, values.can_move(direction));
+            }
+        }
+    }
+
+    #[test]
+    fn terminal_test() {
+        assert!(Board::from_values(4, 4, &[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 1]).is_terminal());
+        assert!(Board::from_values(3, 2, &[1, 2, 3, 3, 1, 2]).is_terminal());
+        assert!(!Board::from_values(2, 3, &[1, 2, 3, 2, 4, 5]).is_terminal());
+        assert!(!Board::from_values(4, 4, &[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 0]).is_terminal());
+    }
+
+    #[test]
+    fn spawn_tile_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut values = Board::from_values(2, 2, &[1, 0, 1, 1]);
+
+        let spawn = values.spawn_tile(&mut rng).unwrap();
+        assert_eq!((spawn.col, spawn.row), (1, 0));
+        assert_eq!(values[1], Some(spawn.value));
+        assert_eq!(values.spawn_tile(&mut rng), None);
+
+        // Same seed gives the same spawns
+        let spawns = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut values = Board::new(4, 4);
+            (0..16).map(|_| values.spawn_tile(&mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(spawns(42), spawns(42));
+    }
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use crate::game::components::*;
+use crate::game::playback::playing_replay;
+use crate::game::states::*;
+use crate::game::systems::input::move_input_system;
+
+/// Stick pushed less than that is at rest, stick position goes from 0 to 1
+pub const STICK_DEADZONE: f32 = 0.5;
+
+/// Direction the stick is pushed to, `None` inside the deadzone
+pub fn stick_direction(stick: Vec2, deadzone: f32) -> Option<Direction> {
+    if stick.length() < deadzone {
+        return None;
+    }
+
+    let direction = match (stick.x.abs() >= stick.y.abs(), stick.x > 0., stick.y > 0.) {
+        (true, true, _) => Direction::Right,
+        (true, false, _) => Direction::Left,
+        // Stick goes up with positive y, unlike the window
+        (false, _, true) => Direction::Up,
+        (false, _, false) => Direction::Down,
+    };
+    Some(direction)
+}
+
+/// Stick direction when it changes, so holding the stick makes one move instead of one every frame
+pub fn guard_repeat(current: Option<Direction>, held: &mut Option<Direction>) -> Option<Direction> {
+    let new = current.filter(|direction| *held != Some(*direction));
+    *held = current;
+    new
+}
+
+/// Direction pressed on the D-pad of any gamepad, or the left stick pushed since the last frame
+pub fn gamepad_direction<'a>(
+    gamepads: impl IntoIterator<Item = &'a Gamepad>,
+    held: &mut Option<Direction>,
+) -> Option<Direction> {
+    let mut pressed = None;
+    let mut stick = None;
+    for gamepad in gamepads {
+        pressed = pressed.or_else(|| {
+            [
+                (GamepadButton::DPadLeft, Direction::Left),
+                (GamepadButton::DPadRight, Direction::Right),
+                (GamepadButton::DPadDown, Direction::Down),
+                (GamepadButton::DPadUp, Direction::Up),
+            ]
+            .into_iter()
+            .find(|(button, _)| gamepad.just_pressed(*button))
+            .map(|(_, direction)| direction)
+        });
+        stick = stick.or(stick_direction(gamepad.left_stick(), STICK_DEADZONE));
+    }
+
+    pressed.or(guard_repeat(stick, held))
+}
+
+pub fn gamepad_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            gamepad_move_system
+                .before(move_input_system)
+                .run_if(
+                    in_state(GameState::Wait)
+                        .or(in_state(GameState::Process))
+                        .or(in_state(GameState::Movement))
+                        .or(in_state(GameState::Decision)),
+                ),
+            gamepad_button_system.run_if(in_state(GameState::Wait).or(in_state(GameState::Lose))),
+        )
+            .run_if(not(playing_replay))
+            .in_set(InputSet)
+            .in_set(GameSet),
+    );
+}
+
+/// D-pad and left stick move tiles
+fn gamepad_move_system(
+    gamepads: Query<&Gamepad>,
+    mut held: Local<Option<Direction>>,
+    mut move_input_messages: MessageWriter<MoveInputMessage>,
+) {
+    if let Some(direction) = gamepad_direction(gamepads, &mut held) {
+        move_input_messages.write(MoveInputMessage(direction));
+    }
+}
+
+/// West button undoes, east redoes and north starts a new game
+fn gamepad_button_system(
+    gamepads: Query<&Gamepad>,
+    mut history_messages: MessageWriter<HistoryMessage>,
+    mut new_game_messages: MessageWriter<NewGameMessage>,
+) {
+    for gamepad in &gamepads {
+        if gamepad.just_pressed(GamepadButton::West) {
+            history_messages.write(HistoryMessage::Undo);
+        } else if gamepad.just_pressed(GamepadButton::East) {
+            history_messages.write(HistoryMessage::Redo);
+        } else if gamepad.just_pressed(GamepadButton::North) {
+            new_game_messages.write(NewGameMessage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stick_direction_test() {
+        assert_eq!(stick_direction(Vec2::new(0.3, -0.2), STICK_DEADZONE), None);
+        assert_eq!(stick_direction(Vec2::new(0.9, 0.4), STICK_DEADZONE), Some(Direction::Right));
+        assert_eq!(stick_direction(Vec2::new(-0.6, 0.1), STICK_DEADZONE), Some(Direction::Left));
+        assert_eq!(stick_direction(Vec2::new(0.2, 0.8), STICK_DEADZONE), Some(Direction::Up));
+        assert_eq!(stick_direction(Vec2::new(-0.1, -1.), STICK_DEADZONE), Some(Direction::Down));
+    }
+
+    #[test]
+    fn guard_repeat_test() {
+        let mut held = None;
+        let stick = [None, Some(Direction::Up), Some(Direction::Up), Some(Direction::Left), None, Some(Direction::Left)];
+        let moves: Vec<_> = stick.into_iter().map(|current| guard_repeat(current, &mut held)).collect();
+
+        assert_eq!(moves, vec![None, Some(Direction::Up), None, Some(Direction::Left), None, Some(Direction::Left)]);
+    }
+}
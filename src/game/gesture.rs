@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use crate::game::components::*;
+use crate::game::playback::playing_replay;
+use crate::game::states::*;
+use crate::game::systems::input::move_input_system;
+
+/// Position of the pointer at a moment, in window coordinates with y going down
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PointerSample {
+    pub position: Vec2,
+    /// Time since start, in seconds
+    pub time: f32,
+}
+
+/// What counts as a swipe, distances are in logical pixels
+#[derive(Resource, Copy, Clone, PartialEq, Debug)]
+pub struct SwipeSettings {
+    /// Shorter swipes are taps and clicks
+    pub min_distance: f32,
+    /// Slower swipes are the pointer resting or wandering, in pixels per second
+    pub min_velocity: f32,
+    /// How many times longer the swipe has to be along its axis than across it
+    pub dominance: f32,
+}
+
+impl Default for SwipeSettings {
+    fn default() -> Self {
+        Self {
+            min_distance: 40.,
+            min_velocity: 150.,
+            dominance: 1.5,
+        }
+    }
+}
+
+/// Direction of the swipe made by the samples from press to release, `None` when it isn't a swipe
+///
+/// Only the first and the last sample matter for distance and velocity, the swipe has to go mostly
+/// along one axis by [SwipeSettings::dominance].
+pub fn recognize_swipe(samples: &[PointerSample], settings: &SwipeSettings) -> Option<Direction> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let delta = last.position - first.position;
+    let horizontal = delta.x.abs() >= delta.y.abs();
+    let (along, across) = if horizontal { (delta.x, delta.y) } else { (delta.y, delta.x) };
+
+    let distance = along.abs();
+    if distance < settings.min_distance || distance < across.abs() * settings.dominance {
+        return None;
+    }
+    let duration = (last.time - first.time).max(f32::EPSILON);
+    if distance / duration < settings.min_velocity {
+        return None;
+    }
+
+    let direction = match (horizontal, along > 0.) {
+        (true, true) => Direction::Right,
+        (true, false) => Direction::Left,
+        // Window coordinates go down
+        (false, true) => Direction::Down,
+        (false, false) => Direction::Up,
+    };
+    Some(direction)
+}
+
+/// Touch or mouse button being followed
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Pointer {
+    Mouse,
+    Touch(u64),
+}
+
+/// Samples of the pointer since it was pressed, one pointer at a time
+#[derive(Resource, Default)]
+pub struct PointerTrack {
+    pointer: Option<Pointer>,
+    samples: Vec<PointerSample>,
+}
+
+pub fn gesture_plugin(app: &mut App) {
+    app.init_resource::<SwipeSettings>()
+        .init_resource::<PointerTrack>()
+        .add_systems(
+            Update,
+            track_pointer_system
+                .before(move_input_system)
+                .run_if(not(playing_replay))
+                .in_set(InputSet)
+                .in_set(GameSet),
+        );
+}
+
+/// Follows the first touch or the left mouse button from press to release
+fn track_pointer_system(
+    time: Res<Time>,
+    touches: Res<Touches>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<SwipeSettings>,
+    mut track: ResMut<PointerTrack>,
+    mut move_input_messages: MessageWriter<MoveInputMessage>,
+) {
+    let cursor = window_query.single().ok().and_then(Window::cursor_position);
+    let sample = |position| PointerSample { position, time: time.elapsed_secs() };
+
+    let released = match track.pointer {
+        None => {
+            if let Some(touch) = touches.iter_just_pressed().next() {
+                track.pointer = Some(Pointer::Touch(touch.id()));
+                track.samples = vec![sample(touch.position())];
+            } else if let Some(position) = cursor.filter(|_| mouse_input.just_pressed(MouseButton::Left)) {
+                track.pointer = Some(Pointer::Mouse);
+                track.samples = vec![sample(position)];
+            }
+            false
+        }
+        Some(Pointer::Touch(id)) => {
+            let touch = touches.get_pressed(id).or_else(|| touches.get_released(id));
+            if let Some(touch) = touch {
+                track.samples.push(sample(touch.position()));
+            } else {
+                // Canceled touch isn't a swipe
+                *track = PointerTrack::default();
+            }
+            touches.just_released(id)
+        }
+        Some(Pointer::Mouse) => {
+            if let Some(position) = cursor {
+                track.samples.push(sample(position));
+            }
+            !mouse_input.pressed(MouseButton::Left)
+        }
+    };
+    if !released {
+        return;
+    }
+
+    let PointerTrack { samples, .. } = std::mem::take(track.as_mut());
+    if let Some(direction) = recognize_swipe(&samples, &settings) {
+        move_input_messages.write(MoveInputMessage(direction));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples from `from` to `to` taking `duration` seconds, with one sample in between
+    fn samples(from: (f32, f32), to: (f32, f32), duration: f32) -> Vec<PointerSample> {
+        let (from, to) = (Vec2::new(from.0, from.1), Vec2::new(to.0, to.1));
+        vec![
+            PointerSample { position: from, time: 1. },
+            PointerSample { position: from.lerp(to, 0.5), time: 1. + duration / 2. },
+            PointerSample { position: to, time: 1. + duration },
+        ]
+    }
+
+    #[test]
+    fn recognize_swipe_test() {
+        let settings = SwipeSettings::default();
+
+        assert_eq!(recognize_swipe(&samples((100., 100.), (200., 110.), 0.2), &settings), Some(Direction::Right));
+        assert_eq!(recognize_swipe(&samples((100., 100.), (20., 90.), 0.2), &settings), Some(Direction::Left));
+        assert_eq!(recognize_swipe(&samples((100., 100.), (105., 200.), 0.2), &settings), Some(Direction::Down));
+        assert_eq!(recognize_swipe(&samples((100., 100.), (100., 0.), 0.2), &settings), Some(Direction::Up));
+    }
+
+    #[test]
+    fn not_a_swipe_test() {
+        let settings = SwipeSettings::default();
+
+        assert_eq!(recognize_swipe(&[], &settings), None);
+        // Tap or click
+        assert_eq!(recognize_swipe(&samples((100., 100.), (100., 100.), 0.1), &settings), None);
+        assert_eq!(recognize_swipe(&samples((100., 100.), (120., 100.), 0.1), &settings), None);
+        // Slow drag
+        assert_eq!(recognize_swipe(&samples((100., 100.), (200., 100.), 2.), &settings), None);
+        // Diagonal
+        assert_eq!(recognize_swipe(&samples((100., 100.), (200., 180.), 0.2), &settings), None);
+        // Same time stamps are fast, not a division by zero
+        assert_eq!(recognize_swipe(&samples((0., 0.), (0., 50.), 0.), &settings), Some(Direction::Down));
+    }
+}
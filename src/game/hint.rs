@@ -0,0 +1,132 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use crate::game::ai::{AiConfig, SteppedSearch, FRAME_SEARCH_TIME};
+use crate::game::components::*;
+use crate::game::controls::{Action, KeyBindings};
+use crate::game::playback::playing_replay;
+use crate::game::states::*;
+use crate::game::systems::game_logic::values_board;
+use crate::menu::AppState;
+
+/// Search for a hint, going on a bit every frame until it's done
+#[derive(Resource, Default)]
+pub struct PendingHint(Option<SteppedSearch>);
+
+/// Marker for the hint arrow and its text over the board
+#[derive(Component)]
+pub struct HintArrow;
+
+pub fn hint_plugin(app: &mut App) {
+    app.init_resource::<AiConfig>()
+        .init_resource::<PendingHint>()
+        .add_systems(
+            Update,
+            (
+                request_hint_system
+                    .run_if(in_state(GameState::Wait).and(not(playing_replay)))
+                    .in_set(InputSet),
+                show_hint_system.run_if(|pending_hint: Res<PendingHint>| pending_hint.0.is_some()),
+            )
+                .chain()
+                .in_set(GameSet),
+        )
+        // Hint is only good for the board it was searched for
+        .add_systems(Update, clear_hint_system.run_if(resource_changed::<History>).in_set(GameSet))
+        .add_systems(OnExit(GameState::Wait), clear_hint_system)
+        .add_systems(OnExit(AppState::Game), clear_hint_system);
+}
+
+fn request_hint_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    ai_config: Res<AiConfig>,
+    board_state_resource: Res<BoardStateResource>,
+    mut pending_hint: ResMut<PendingHint>,
+    block_query: Query<&Value, With<Block>>,
+) {
+    if !key_bindings.just_pressed(Action::Hint, &keyboard_input) || pending_hint.0.is_some() {
+        return;
+    }
+
+    let values = values_board(&board_state_resource.0, &block_query);
+    pending_hint.0 = Some(SteppedSearch::new(&values, &ai_config));
+}
+
+fn show_hint_system(
+    mut commands: Commands,
+    layout: Res<BoardLayout>,
+    mut pending_hint: ResMut<PendingHint>,
+    arrow_query: Query<Entity, With<HintArrow>>,
+) {
+    let Some(result) = pending_hint.0.as_mut().and_then(|search| search.step(FRAME_SEARCH_TIME)) else {
+        return;
+    };
+    pending_hint.0 = None;
+    for entity in &arrow_query {
+        commands.entity(entity).despawn();
+    }
+
+    let Some(hint) = result else {
+        debug!("No move to hint");
+        return;
+    };
+    debug!("Hint {:?} with expected value {:.2} at depth {}", hint.direction, hint.expected_value, hint.depth);
+
+    let size = layout.tile_size;
+    // Arrow pointing up, turned towards the direction
+    let arrow = shapes::Polygon {
+        points: vec![
+            Vec2::new(-0.15, -0.6),
+            Vec2::new(0.15, -0.6),
+            Vec2::new(0.15, 0.1),
+            Vec2::new(0.35, 0.1),
+            Vec2::new(0., 0.6),
+            Vec2::new(-0.35, 0.1),
+            Vec2::new(-0.15, 0.1),
+        ]
+        .into_iter()
+        .map(|point| point * size)
+        .collect(),
+        closed: true,
+    };
+    let angle = match hint.direction {
+        Direction::Up => 0.,
+        Direction::Left => FRAC_PI_2,
+        Direction::Down => PI,
+        Direction::Right => -FRAC_PI_2,
+    };
+
+    commands.spawn((
+        ShapeBuilder::with(&arrow)
+            .fill(Color::srgba(1., 1., 1., 0.6))
+            .stroke((Color::BLACK, 6. * layout.scale()))
+            .build(),
+        Transform::from_translation(layout.center.extend(20.)).with_rotation(Quat::from_rotation_z(angle)),
+        HintArrow,
+        OnGameScreen,
+    ));
+    commands.spawn((
+        Text2d::new(format!("{:.1}", hint.expected_value)),
+        TextFont {
+            font_size: 48. * layout.scale(),
+            ..default()
+        },
+        TextColor(Color::BLACK),
+        Transform::from_translation(layout.center.extend(21.)),
+        HintArrow,
+        OnGameScreen,
+    ));
+}
+
+fn clear_hint_system(
+    mut commands: Commands,
+    mut pending_hint: ResMut<PendingHint>,
+    arrow_query: Query<Entity, With<HintArrow>>,
+) {
+    // Search for the old board is dropped
+    pending_hint.0 = None;
+    for entity in &arrow_query {
+        commands.entity(entity).despawn();
+    }
+}
@@ -1,32 +1,69 @@
+pub mod ai;
+pub mod autoplay;
+pub mod bitboard;
 mod components;
+pub mod controls;
 pub mod effects;
+pub mod engine;
+pub mod gamepad;
+mod gesture;
+mod hint;
+pub mod mode;
+mod pause;
+pub mod playback;
+pub mod replay;
+pub mod rules;
+pub mod save;
+pub mod simulation;
+pub mod spawner;
 mod sprites;
 mod states;
+pub mod stats;
 mod systems;
 mod utils;
 
+pub use crate::game::components::{
+    Board, BoardSize, Direction, InputBuffer, SeedSetting, SnapMoves, MAX_BOARD_SIZE, MAX_INPUT_BUFFER, MIN_BOARD_SIZE,
+};
+pub use crate::game::states::{GameState, PauseState};
+
+use crate::game::autoplay::autoplay_plugin;
 use crate::game::components::*;
+use crate::game::controls::controls_plugin;
+use crate::game::gamepad::gamepad_plugin;
+use crate::game::gesture::gesture_plugin;
+use crate::game::hint::hint_plugin;
+use crate::game::mode::{Endless, GameMode};
+use crate::game::pause::{pause_plugin, PauseButton};
+use crate::game::playback::{playback_plugin, playback_setup, playback_ui_setup, playing_replay};
+use crate::game::replay::{record_move_system, replay_plugin, start_recording_system};
+use crate::game::rules::{RulesResource, WinTarget};
+use crate::game::save::{continuing_game, resume_game_setup, save_game_system, save_plugin};
+use crate::game::spawner::SpawnerResource;
 use crate::game::sprites::sprites_plugin;
 use crate::game::states::*;
+use crate::game::stats::{stats_plugin, Statistics};
 use crate::game::systems::effect::*;
 use crate::game::systems::game_logic::*;
+use crate::game::systems::history::*;
 use crate::game::systems::input::*;
 use crate::game::systems::movement::*;
+use crate::game::systems::overlay::*;
 use crate::game::systems::process::*;
+use crate::game::systems::score::*;
 use crate::game::utils::*;
 use crate::menu::{despawn_screen, AppState};
 use bevy::app::App;
 use bevy::color::Color;
+use bevy::ecs::schedule::ScheduleConfigs;
+use bevy::ecs::system::ScheduleSystem;
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use rstar::{Point, RTree};
 use crate::SharedRand;
 
-// Constants that interchangeable during the run
-const SIZE: usize = 4;
+// Size of block sprites, blocks are scaled to the tile size of the board
 const RECT_SIZE: f32 = 250.;
-const X_SHIFT: f32 = RECT_SIZE * 2.;
-const Y_SHIFT: f32 = RECT_SIZE * 1.5;
 
 #[derive(Clone, PartialEq, Debug)]
 struct TreeNode {
@@ -55,26 +92,90 @@ impl Point for TreeNode {
 }
 
 pub fn game_plugin(app: &mut App) {
-    app.add_plugins((sprites_plugin, ShapePlugin))
-        .insert_resource(BoardStateResource(Board::<SIZE, Entity>(vec![
-            None;
-            SIZE * SIZE
-        ])))
+    app.add_plugins((sprites_plugin, stats_plugin, save_plugin, replay_plugin, playback_plugin, hint_plugin, autoplay_plugin, gesture_plugin, gamepad_plugin, controls_plugin, pause_plugin, ShapePlugin))
+        .insert_resource(BoardStateResource(Board::new(4, 4)))
+        .init_resource::<BoardSize>()
+        .init_resource::<BoardLayout>()
         .insert_resource(GameParams { move_time: 1. })
+        .init_resource::<Score>()
+        .init_resource::<Moves>()
+        .init_resource::<PlayTime>()
+        .init_resource::<GameMode>()
+        .init_resource::<Endless>()
+        .init_resource::<WinTarget>()
+        .init_resource::<GameSeed>()
+        .init_resource::<SeedSetting>()
+        .init_resource::<RulesResource>()
+        .init_resource::<SpawnerResource>()
+        .init_resource::<History>()
+        .init_resource::<ForcedSpawn>()
+        .init_resource::<InputBuffer>()
+        .init_resource::<SnapMoves>()
+        .init_resource::<InputQueue>()
         .add_message::<CollisionMessage>()
         .add_message::<DirectionMessage>()
+        .add_message::<MoveInputMessage>()
         .add_message::<QueuedMoveMessage>()
         .add_message::<MergeEffectMessage>()
+        .add_message::<MergeMessage>()
+        .add_message::<HistoryMessage>()
+        .add_message::<LossUndoneMessage>()
+        .add_message::<NewGameMessage>()
+        .add_message::<SpawnMessage>()
         .init_state::<GameState>()
-        .add_systems(OnEnter(AppState::Game), (board_setup, game_ui_setup))
-        .configure_sets(Update, GameSet.run_if(in_state(AppState::Game)))
-        .configure_sets(FixedUpdate, GameSet.run_if(in_state(AppState::Game)))
+        .init_state::<PauseState>()
+        .add_systems(
+            OnEnter(AppState::Game),
+            (
+                game_setup_systems(),
+                game_ui_setup.after(board_setup).after(resume_game_setup).after(playback_setup),
+                playback_ui_setup.after(game_ui_setup).run_if(playing_replay),
+            ),
+        )
+        .configure_sets(Update, GameSet.run_if(in_state(AppState::Game).and(in_state(PauseState::Running))))
+        .configure_sets(FixedUpdate, GameSet.run_if(in_state(AppState::Game).and(in_state(PauseState::Running))))
         // Input stuff
         .add_systems(
             Update,
-            generate_direction_messages
-                .run_if(in_state(GameState::Wait))
-                .in_set(InputSet),
+            (
+                generate_direction_messages.run_if(in_state(GameState::Wait)),
+                buffer_direction_input.run_if(
+                    in_state(GameState::Process)
+                        .or(in_state(GameState::Movement))
+                        .or(in_state(GameState::Decision)),
+                ),
+                move_input_system
+                    .after(generate_direction_messages)
+                    .run_if(
+                        in_state(GameState::Wait)
+                            .or(in_state(GameState::Process))
+                            .or(in_state(GameState::Movement))
+                            .or(in_state(GameState::Decision)),
+                    ),
+            )
+                .run_if(not(playing_replay))
+                .in_set(InputSet)
+                .in_set(GameSet),
+        )
+        // Buffered moves don't outlive the board they were pressed for
+        .add_systems(
+            Update,
+            clear_input_queue.run_if(on_message::<HistoryMessage>.or(on_message::<NewGameMessage>)).in_set(GameSet),
+        )
+        .add_systems(OnEnter(GameState::Win), clear_input_queue)
+        .add_systems(OnEnter(GameState::Lose), clear_input_queue)
+        .add_systems(OnExit(AppState::Game), clear_input_queue)
+        // Undo, redo and restart are available while waiting for input and after the loss
+        .add_systems(
+            Update,
+            (
+                (generate_history_messages, generate_restart_messages, game_button_system).in_set(InputSet),
+                history_system,
+            )
+                .chain()
+                .run_if(in_state(GameState::Wait).or(in_state(GameState::Lose)))
+                .run_if(not(playing_replay))
+                .in_set(GameSet),
         )
         // Game processes and preparations for movement section
         .add_systems(
@@ -89,48 +190,136 @@ pub fn game_plugin(app: &mut App) {
         // Section for UI updates (movement and such)
         .add_systems(
             Update,
-            (collision_system, process_collision_messages_system, merge_effect_system, queued_movement_system, queued_system_finished)
+            (collision_system, process_collision_messages_system, merge_effect_system, queued_movement_system, score_system, queued_system_finished)
                 .chain()
                 .run_if(in_state(GameState::Movement))
                 .in_set(GameSet)
         )
+        .add_systems(
+            Update,
+            (
+                update_score_text.run_if(resource_changed::<Score>),
+                update_best_score_text.run_if(resource_changed::<Statistics>),
+                update_seed_text.run_if(resource_changed::<GameSeed>),
+                play_time_system.run_if(not(in_state(GameState::Win)).and(not(in_state(GameState::Lose)))),
+            )
+                .in_set(GameSet),
+        )
         .add_systems(Update, log_transitions::<GameState>)
         .add_systems(
             OnEnter(GameState::Decision),
-            (produce_new_tile_system, the_end_system)
+            (
+                produce_new_tile_system,
+                record_move_system,
+                count_move_system,
+                the_end_system,
+                record_snapshot_system,
+                save_game_system.run_if(not(playing_replay)),
+            )
                 .chain()
                 .in_set(GameSet),
         )
-        // TODO: Win/Lose should trigger effects, need to do something about that in OnEnter functions for Win/Lose states.
-        .add_systems(OnExit(AppState::Game), despawn_screen::<OnGameScreen>);
+        // Starting over without leaving the game screen
+        .add_systems(
+            Update,
+            (
+                clear_board_system,
+                reset_score,
+                reset_history,
+                seed_setup,
+                spawner_setup,
+                board_setup,
+                start_recording_system,
+                record_snapshot_system,
+            )
+                .chain()
+                .run_if(on_message::<NewGameMessage>)
+                .in_set(GameSet),
+        )
+        // Overlays with the summary of the finished game, the end of a replay keeps its timeline instead
+        .add_systems(OnEnter(GameState::Win), win_overlay_setup.run_if(not(playing_replay)))
+        .add_systems(OnEnter(GameState::Lose), lose_overlay_setup.run_if(not(playing_replay)))
+        .add_systems(
+            Update,
+            overlay_button_system
+                .before(history_system)
+                .run_if(in_state(GameState::Win).or(in_state(GameState::Lose)))
+                .in_set(GameSet),
+        )
+        .add_systems(OnExit(GameState::Win), despawn_screen::<OnWinOverlay>)
+        .add_systems(OnExit(GameState::Lose), despawn_screen::<OnLoseOverlay>)
+        .add_systems(OnExit(AppState::Game), (despawn_screen::<OnGameScreen>, reset_game_system));
+}
+
+/// Game state and the board of the started game, new, continued or played back
+fn game_setup_systems() -> ScheduleConfigs<ScheduleSystem> {
+    (
+        reset_score,
+        reset_history,
+        (
+            (seed_setup, spawner_setup, board_setup)
+                .chain()
+                .run_if(not(continuing_game).and(not(playing_replay))),
+            resume_game_setup.run_if(continuing_game),
+            playback_setup.run_if(playing_replay),
+        ),
+        // Mode of continued and played back games is only known now
+        spawner_setup.run_if(continuing_game.or(playing_replay)),
+        rules_setup,
+        start_recording_system,
+        record_snapshot_system,
+    )
+        .chain()
+        .into_configs()
 }
 
 fn board_setup(
     mut commands: Commands,
+    board_size: Res<BoardSize>,
+    mut layout: ResMut<BoardLayout>,
     mut shared_rand: ResMut<SharedRand>,
+    mut spawner: ResMut<SpawnerResource>,
     mut board_state_resource: ResMut<BoardStateResource>,
+    mut endless: ResMut<Endless>,
 ) {
-    let board = &mut board_state_resource.0;
-
-    let Some((col1, row1, val1)) = acquire_empty_tile(shared_rand.as_mut(), board) else { panic!("No empty tile during board setup") };
-    let entity1 = commands.spawn(produce_block_bundle(col1, row1, val1)).id();
-    board[col1 + row1 * SIZE] = Some(entity1);
-    trace!("Board at {}x{} filled with {}", col1, row1, val1);
+    endless.0 = false;
+    let width = board_size.width.clamp(MIN_BOARD_SIZE, MAX_BOARD_SIZE);
+    let height = board_size.height.clamp(MIN_BOARD_SIZE, MAX_BOARD_SIZE);
+    *layout = BoardLayout::new(width, height);
+    // Two spawns to start with
+    let mut values = Board::new(width, height);
+    for _ in 0..2 {
+        let spawns = spawner.place(&mut values, &mut shared_rand.0);
+        if spawns.is_empty() {
+            panic!("No empty tile during board setup")
+        }
+        for spawn in spawns {
+            trace!("Board at {}x{} filled with {}", spawn.col, spawn.row, spawn.value);
+        }
+    }
 
-    let Some((col2, row2, val2)) = acquire_empty_tile(shared_rand.as_mut(), board) else { panic!("No empty tile during board setup") };
-    let entity2 = commands.spawn(produce_block_bundle(col2, row2, val2)).id();
-    board[col2 + row2 * SIZE] = Some(entity2);
-    trace!("Board at {}x{} filled with {}", col2, row2, val2);
+    let board = &mut board_state_resource.0;
+    *board = Board::new(width, height);
+    for (index, value) in values.iter().enumerate() {
+        board[index] = value.map(|value| {
+            commands
+                .spawn(produce_block_bundle(index % width, index / width, value, &layout))
+                .id()
+        });
+    }
 }
 
 fn game_ui_setup(
     mut commands: Commands,
+    layout: Res<BoardLayout>,
+    statistics: Res<Statistics>,
+    game_seed: Res<GameSeed>,
 ) {
     // Board
-    for i in 0..SIZE {
-        for j in 0..SIZE {
+    for i in 0..layout.width {
+        for j in 0..layout.height {
             let rect = shapes::Rectangle {
-                extents: Vec2::splat(RECT_SIZE),
+                extents: Vec2::splat(layout.tile_size),
                 origin: RectangleOrigin::Center,
                 radii: None,
             };
@@ -138,11 +327,11 @@ fn game_ui_setup(
             commands.spawn((
                 ShapeBuilder::with(&rect)
                     .fill(Color::srgba(0f32, 0f32, 0f32, 0.5))
-                    .stroke((Color::WHITE, 10.0))
+                    .stroke((Color::WHITE, 10.0 * layout.scale()))
                     .build(),
                 Transform::from_xyz(
-                    col_to_x(i as i32, RECT_SIZE),
-                    row_to_y(j as i32, RECT_SIZE),
+                    col_to_x(i as i32, &layout),
+                    row_to_y(j as i32, &layout),
                     0.0,
                 ),
                 OnGameScreen,
@@ -163,7 +352,6 @@ fn game_ui_setup(
             OnGameScreen,
         ))
         .with_children(|parent| {
-            #[cfg(not(target_arch = "wasm32"))]
             // First create a `Node` for centering what we want to display
             parent
                 .spawn((
@@ -190,11 +378,11 @@ fn game_ui_setup(
                             ..default()
                         },
                         BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
-                        crate::menu::menu_mod::MenuButtonAction::Quit,
+                        PauseButton,
                     ))
                     .with_children(|parent| {
                         parent.spawn((
-                            Text::new("X"),
+                            Text::new("||"),
                             TextFont {
                                 font_size: 33.,
                                 ..default()
@@ -203,18 +391,131 @@ fn game_ui_setup(
                         ));
                     });
                 });
+
+            // Score display
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Start,
+                        padding: UiRect::all(Val::Px(20.)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::BLACK),
+                ))
+                .with_children(|p| {
+                    p.spawn((
+                        Text::new("Score"),
+                        TextFont {
+                            font_size: 25.,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(0.6, 0.6, 0.6)),
+                    ));
+                    p.spawn((
+                        Text::new("0"),
+                        TextFont {
+                            font_size: 33.,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                        ScoreText,
+                    ));
+                    p.spawn((
+                        Text::new("Best"),
+                        TextFont {
+                            font_size: 25.,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(0.6, 0.6, 0.6)),
+                    ));
+                    p.spawn((
+                        Text::new(statistics.best_score.to_string()),
+                        TextFont {
+                            font_size: 33.,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                        BestScoreText,
+                    ));
+                    p.spawn((
+                        Text::new("Seed"),
+                        TextFont {
+                            font_size: 25.,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(0.6, 0.6, 0.6)),
+                    ));
+                    p.spawn((
+                        Text::new(game_seed.0.to_string()),
+                        TextFont {
+                            font_size: 25.,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                        SeedText,
+                    ));
+                });
+
+            // History buttons
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Start,
+                        ..default()
+                    },
+                    BackgroundColor(Color::BLACK),
+                ))
+                .with_children(|p| {
+                    for (action, text) in [(GameButtonAction::Undo, "Undo"), (GameButtonAction::Redo, "Redo")] {
+                        p.spawn((
+                            Button,
+                            Node {
+                                width: Val::Px(120.),
+                                height: Val::Px(65.),
+                                margin: UiRect::all(Val::Px(20.)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+                            action,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                Text::new(text),
+                                TextFont {
+                                    font_size: 33.,
+                                    ..default()
+                                },
+                                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                            ));
+                        });
+                    }
+                });
         });
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use crate::game::controls::KeyBindings;
+    use crate::game::effects::EffectMarker;
     use bevy::state::app::StatesPlugin;
     use bevy::time::TimePlugin;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
     fn common_app_setup(app: &mut App, board_vec: Vec<usize>) {
+        common_app_setup_sized(app, 4, 4, board_vec);
+    }
+
+    fn common_app_setup_sized(app: &mut App, width: usize, height: usize, board_vec: Vec<usize>) {
         app.add_message::<DirectionMessage>();
         app.add_message::<QueuedMoveMessage>();
+        app.add_message::<MergeMessage>();
 
         let board_vec: Vec<usize> = board_vec;
         let board_vec: Vec<Option<Entity>> = board_vec
@@ -224,7 +525,12 @@ mod tests {
                 if x != 0 {
                     Some(
                         app.world_mut()
-                            .spawn(produce_block_bundle(i / 4, i % 4, x))
+                            .spawn(produce_block_bundle(
+                                i % width,
+                                i / width,
+                                x,
+                                &BoardLayout::new(width, height),
+                            ))
                             .id(),
                     )
                 } else {
@@ -232,8 +538,11 @@ mod tests {
                 }
             })
             .collect();
-        app.insert_resource(BoardStateResource(Board(board_vec.clone())));
+        app.insert_resource(BoardStateResource(Board::from_cells(width, height, board_vec.clone())));
+        app.insert_resource(BoardLayout::new(width, height));
         app.insert_resource(GameParams { move_time: 0. });
+        app.init_resource::<Score>();
+        app.init_resource::<Moves>();
         app.init_state::<GameState>();
     }
 
@@ -280,7 +589,7 @@ mod tests {
             2
         );
 
-        let merge_to_entity = board.0[0];
+        let merge_to_entity = board[0];
         assert_eq!(
             app.world_mut()
                 .get::<Value>(merge_to_entity.unwrap())
@@ -310,6 +619,7 @@ mod tests {
                 process_direction_messages,
                 process_queued_move_messages,
                 queued_movement_system,
+                score_system,
                 queued_system_finished,
             )
                 .chain(),
@@ -341,7 +651,7 @@ mod tests {
             1
         );
 
-        let merge_to_entity = board.0[12];
+        let merge_to_entity = board[12];
         assert_eq!(
             app.world_mut()
                 .get::<Value>(merge_to_entity.unwrap())
@@ -349,10 +659,12 @@ mod tests {
                 .0,
             2
         );
-        let merge_with_entity = board.0[4];
+        let merge_with_entity = board[4];
         assert_eq!(merge_with_entity.is_none(), true);
-        let merge_with_entity = board.0[8];
+        let merge_with_entity = board[8];
         assert_eq!(merge_with_entity.is_none(), true);
+
+        assert_eq!(*app.world().resource::<Score>(), Score(4));
     }
 
     #[test]
@@ -371,6 +683,7 @@ mod tests {
                 process_direction_messages,
                 process_queued_move_messages,
                 queued_movement_system,
+                score_system,
                 queued_system_finished,
             )
                 .chain(),
@@ -402,7 +715,7 @@ mod tests {
             2
         );
 
-        let merge_to_entity = board.0[0];
+        let merge_to_entity = board[0];
         assert_eq!(
             app.world_mut()
                 .get::<Value>(merge_to_entity.unwrap())
@@ -410,7 +723,7 @@ mod tests {
                 .0,
             2
         );
-        let merge_with_entity = board.0[4];
+        let merge_with_entity = board[4];
         assert_eq!(
             app.world_mut()
                 .get::<Value>(merge_with_entity.unwrap())
@@ -418,7 +731,291 @@ mod tests {
                 .0,
             1
         );
-        let no_merge_entity = board.0[8];
+        let no_merge_entity = board[8];
         assert_eq!(no_merge_entity.is_none(), true);
+
+        assert_eq!(*app.world().resource::<Score>(), Score(4));
+    }
+
+    #[test]
+    fn merge_on_rectangular_board() {
+        let mut app = App::new();
+
+        app.add_plugins((StatesPlugin, TimePlugin));
+        common_app_setup_sized(
+            &mut app,
+            5,
+            3,
+            vec![1, 1, 0, 0, 0, 0, 0, 0, 0, 2, 1, 0, 1, 0, 1],
+        );
+
+        app.add_systems(
+            Update,
+            (
+                process_direction_messages,
+                process_queued_move_messages,
+                queued_movement_system,
+                score_system,
+                queued_system_finished,
+            )
+                .chain(),
+        );
+
+        app.world_mut()
+            .resource_mut::<Messages<DirectionMessage>>()
+            .write(DirectionMessage(Direction::Right));
+
+        app.update();
+
+        let board = app
+            .world_mut()
+            .resource_mut::<BoardStateResource>()
+            .0
+            .clone();
+        assert_eq!(
+            app.world_mut()
+                .query_filtered::<Entity, With<Block>>()
+                .iter(app.world())
+                .len(),
+            4
+        );
+
+        let occupied: Vec<usize> = (0..board.len()).filter(|&i| board[i].is_some()).collect();
+        assert_eq!(occupied, vec![4, 9, 13, 14]);
+        assert_eq!(app.world().get::<Value>(board[4].unwrap()).unwrap().0, 2);
+        assert_eq!(app.world().get::<Value>(board[9].unwrap()).unwrap().0, 2);
+        assert_eq!(app.world().get::<Value>(board[13].unwrap()).unwrap().0, 1);
+        assert_eq!(app.world().get::<Value>(board[14].unwrap()).unwrap().0, 2);
+
+        assert_eq!(*app.world().resource::<Score>(), Score(8));
+    }
+
+    #[test]
+    fn undo_and_redo_merge() {
+        let mut app = App::new();
+
+        app.add_plugins((StatesPlugin, TimePlugin));
+        common_app_setup(
+            &mut app,
+            vec![1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        );
+        app.add_message::<HistoryMessage>();
+        app.add_message::<LossUndoneMessage>();
+        app.init_resource::<GameMode>();
+        app.init_resource::<History>();
+        app.insert_resource(SharedRand(ChaCha8Rng::seed_from_u64(0)));
+
+        app.add_systems(
+            Update,
+            (
+                process_direction_messages,
+                process_queued_move_messages,
+                queued_movement_system,
+                score_system,
+            )
+                .chain(),
+        );
+
+        let initial_board = app.world().resource::<BoardStateResource>().0.clone();
+        app.world_mut().run_system_once(record_snapshot_system).unwrap();
+
+        app.world_mut()
+            .resource_mut::<Messages<DirectionMessage>>()
+            .write(DirectionMessage(Direction::Up));
+        app.update();
+        app.world_mut().run_system_once(record_snapshot_system).unwrap();
+
+        assert_eq!(*app.world().resource::<Score>(), Score(4));
+        assert_eq!(app.world().resource::<History>().undo.len(), 1);
+
+        app.world_mut()
+            .resource_mut::<Messages<HistoryMessage>>()
+            .write(HistoryMessage::Undo);
+        app.world_mut().run_system_once(history_system).unwrap();
+
+        let board = app.world().resource::<BoardStateResource>().0.clone();
+        assert_eq!(*app.world().resource::<Score>(), Score(0));
+        assert_eq!(board.empty_indices(), initial_board.empty_indices());
+        assert_eq!(app.world().get::<Value>(board[0].unwrap()).unwrap().0, 1);
+        assert_eq!(app.world().get::<Value>(board[4].unwrap()).unwrap().0, 1);
+        assert_eq!(
+            app.world_mut()
+                .query_filtered::<Entity, With<Block>>()
+                .iter(app.world())
+                .len(),
+            2
+        );
+
+        let mut history_messages = app.world_mut().resource_mut::<Messages<HistoryMessage>>();
+        history_messages.clear();
+        history_messages.write(HistoryMessage::Redo);
+        app.world_mut().run_system_once(history_system).unwrap();
+
+        let board = app.world().resource::<BoardStateResource>().0.clone();
+        assert_eq!(*app.world().resource::<Score>(), Score(4));
+        assert_eq!(app.world().get::<Value>(board[0].unwrap()).unwrap().0, 2);
+        assert_eq!(board.empty_indices().len(), 15);
+    }
+
+    #[test]
+    fn undo_out_of_loss() {
+        let mut app = App::new();
+
+        app.add_plugins(StatesPlugin);
+        common_app_setup(
+            &mut app,
+            vec![1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        );
+        app.add_message::<HistoryMessage>();
+        app.add_message::<LossUndoneMessage>();
+        app.init_resource::<GameMode>();
+        app.init_resource::<History>();
+        app.insert_resource(SharedRand(ChaCha8Rng::seed_from_u64(0)));
+        for _ in 0..2 {
+            app.world_mut().run_system_once(record_snapshot_system).unwrap();
+        }
+        let undone_count = |app: &App| {
+            let messages = app.world().resource::<Messages<LossUndoneMessage>>();
+            messages.get_cursor().read(messages).count()
+        };
+
+        app.world_mut().resource_mut::<NextState<GameState>>().set(GameState::Lose);
+        app.update();
+        app.world_mut().resource_mut::<Messages<HistoryMessage>>().write(HistoryMessage::Undo);
+        app.world_mut().run_system_once(history_system).unwrap();
+        assert_eq!(undone_count(&app), 1);
+
+        // Redo while waiting for input isn't about the loss
+        app.update();
+        app.world_mut().resource_mut::<Messages<LossUndoneMessage>>().clear();
+        app.world_mut().resource_mut::<Messages<HistoryMessage>>().write(HistoryMessage::Redo);
+        app.world_mut().run_system_once(history_system).unwrap();
+        assert_eq!(undone_count(&app), 0);
+        assert_eq!(*app.world().resource::<State<GameState>>().get(), GameState::Wait);
+    }
+
+    #[test]
+    fn endless_game_ends_on_stuck_board() {
+        for (endless, state) in [(false, GameState::Win), (true, GameState::Lose)] {
+            let mut app = App::new();
+
+            app.add_plugins(StatesPlugin);
+            common_app_setup(
+                &mut app,
+                vec![1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 11],
+            );
+            app.init_resource::<RulesResource>();
+            app.insert_resource(Endless(endless));
+
+            app.world_mut().run_system_once(the_end_system).unwrap();
+            app.update();
+
+            assert_eq!(*app.world().resource::<State<GameState>>().get(), state, "endless {endless}");
+        }
+    }
+
+    #[test]
+    fn restart_cleans_up_board() {
+        let mut app = App::new();
+
+        app.add_plugins(StatesPlugin);
+        common_app_setup(
+            &mut app,
+            vec![1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0],
+        );
+        let board = app.world().resource::<BoardStateResource>().0.clone();
+        // Block halfway through a merge, with its effect
+        let moving = board[4].unwrap();
+        app.world_mut()
+            .entity_mut(moving)
+            .insert(QueuedMove(Position(0, 0), Timer::from_seconds(1., TimerMode::Once), board[0]))
+            .with_child(EffectMarker);
+        // Effect left behind on its own
+        app.world_mut().spawn(EffectMarker);
+        app.world_mut().resource_mut::<NextState<GameState>>().set(GameState::Lose);
+        app.update();
+
+        app.world_mut().run_system_once(clear_board_system).unwrap();
+        app.update();
+
+        let board = &app.world().resource::<BoardStateResource>().0;
+        assert_eq!((board.width, board.height), (4, 4));
+        assert_eq!(board.empty_indices().len(), 16);
+        let leftovers = app
+            .world_mut()
+            .query_filtered::<Entity, Or<(With<Block>, With<QueuedMove>, With<EffectMarker>)>>()
+            .iter(app.world())
+            .count();
+        assert_eq!(leftovers, 0);
+        assert_eq!(*app.world().resource::<State<GameState>>().get(), GameState::Wait);
+    }
+
+    #[test]
+    fn leaving_game_resets_state() {
+        let mut app = App::new();
+
+        app.add_plugins(StatesPlugin);
+        common_app_setup(
+            &mut app,
+            vec![1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0],
+        );
+        app.init_state::<PauseState>();
+        app.insert_resource(ForcedSpawn(Some(vec![TileSpawn { col: 1, row: 1, value: 1 }])));
+        app.world_mut().resource_mut::<NextState<GameState>>().set(GameState::Lose);
+        app.world_mut().resource_mut::<NextState<PauseState>>().set(PauseState::Paused);
+        app.update();
+
+        app.world_mut().run_system_once(reset_game_system).unwrap();
+        app.update();
+
+        assert_eq!(*app.world().resource::<State<GameState>>().get(), GameState::Wait);
+        assert_eq!(*app.world().resource::<State<PauseState>>().get(), PauseState::Running);
+        assert_eq!(app.world().resource::<BoardStateResource>().0.empty_indices().len(), 16);
+        assert!(app.world().resource::<ForcedSpawn>().0.is_none());
+    }
+
+    #[test]
+    fn buffered_input() {
+        let mut app = App::new();
+
+        app.add_plugins(StatesPlugin);
+        common_app_setup(
+            &mut app,
+            vec![1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        );
+        app.init_resource::<ButtonInput<KeyCode>>();
+        app.init_resource::<KeyBindings>();
+        app.insert_resource(InputBuffer(1));
+        app.insert_resource(SnapMoves(true));
+        app.init_resource::<InputQueue>();
+        let moving = app
+            .world_mut()
+            .spawn(QueuedMove(Position(0, 0), Timer::from_seconds(1., TimerMode::Once), None))
+            .id();
+
+        let press = |app: &mut App, key| {
+            let mut keyboard_input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            keyboard_input.release_all();
+            keyboard_input.clear();
+            keyboard_input.press(key);
+        };
+
+        // Only one of two moves pressed while tiles move fits in the buffer
+        for key in [KeyCode::ArrowUp, KeyCode::ArrowLeft] {
+            press(&mut app, key);
+            app.world_mut().run_system_once(buffer_direction_input).unwrap();
+        }
+        assert_eq!(app.world().resource::<InputQueue>().0, [Direction::Up]);
+        let timer = &app.world().get::<QueuedMove>(moving).unwrap().1;
+        assert_eq!(timer.elapsed(), timer.duration());
+
+        // Buffered move goes first, the new one waits for the next turn
+        press(&mut app, KeyCode::ArrowRight);
+        app.world_mut().run_system_once(generate_direction_messages).unwrap();
+        assert_eq!(app.world().resource::<InputQueue>().0, [Direction::Right]);
+
+        let direction_messages = app.world().resource::<Messages<DirectionMessage>>();
+        let directions: Vec<_> = direction_messages.get_cursor().read(direction_messages).map(|message| message.0).collect();
+        assert_eq!(directions, vec![Direction::Up]);
     }
 }
@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::game::rules::{ClassicRules, Rules, WinTarget};
+use crate::game::spawner::{EvilSpawner, Spawner, WeightedSpawner};
+
+/// Mode of the game session, selected before the game starts
+#[derive(Resource, Component, Serialize, Deserialize, Default, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    /// Deep undo, for learning and experimenting
+    Relaxed,
+    /// No undo at all
+    Hardcore,
+    /// Deep undo and win at 512, whatever the target is
+    Kids,
+    /// Bot plays without a target until the board is stuck, human can take over
+    Autoplay,
+    /// New tiles go where they hurt the most, for players who find classic too easy
+    Evil,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 6] = [
+        GameMode::Classic,
+        GameMode::Relaxed,
+        GameMode::Hardcore,
+        GameMode::Kids,
+        GameMode::Autoplay,
+        GameMode::Evil,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Relaxed => "Relaxed",
+            GameMode::Hardcore => "Hardcore",
+            GameMode::Kids => "Kids",
+            GameMode::Autoplay => "Autoplay",
+            GameMode::Evil => "Evil",
+        }
+    }
+
+    /// How many moves back can be undone
+    pub fn undo_depth(&self) -> usize {
+        match self {
+            GameMode::Classic | GameMode::Autoplay | GameMode::Evil => 8,
+            GameMode::Relaxed | GameMode::Kids => 256,
+            GameMode::Hardcore => 0,
+        }
+    }
+
+    /// End of game conditions of the mode, `target` is the chosen winning tile
+    pub fn rules(&self, target: WinTarget) -> Box<dyn Rules> {
+        match self {
+            GameMode::Kids => Box::new(ClassicRules::new(WinTarget(Some(9)))),
+            GameMode::Autoplay => Box::new(ClassicRules::new(WinTarget(None))),
+            _ => Box::new(ClassicRules::new(target)),
+        }
+    }
+
+    /// New tiles of the mode
+    pub fn spawner(&self) -> Box<dyn Spawner> {
+        match self {
+            // 4 in one of three spawns, bigger tiles come sooner
+            GameMode::Kids => Box::new(WeightedSpawner::new(vec![(1, 2), (2, 1)])),
+            GameMode::Evil => Box::new(EvilSpawner::default()),
+            _ => Box::new(WeightedSpawner::classic()),
+        }
+    }
+}
+
+/// Play goes on after reaching the target, without checking for win
+#[derive(Resource, Serialize, Deserialize, Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Endless(pub bool);
@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use crate::game::components::*;
+use crate::game::controls::{Action, KeyBindings};
+use crate::game::states::*;
+use crate::game::systems::overlay::{spawn_overlay, spawn_overlay_button};
+use crate::menu::{despawn_screen, AppState, MenuState};
+
+/// Marker for entities of the overlay shown while paused
+#[derive(Component)]
+pub struct OnPauseOverlay;
+
+/// Marker for the button on game screen that pauses the game
+#[derive(Component)]
+pub struct PauseButton;
+
+/// Actions of buttons on the pause overlay
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PauseButtonAction {
+    Resume,
+    Restart,
+    Settings,
+    MainMenu,
+}
+
+pub fn pause_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            // Finished games have overlays of their own, settings opened from pause are closed with their back button
+            pause_input_system.run_if(
+                in_state(MenuState::Disabled)
+                    .and(not(in_state(GameState::Win)))
+                    .and(not(in_state(GameState::Lose))),
+            ),
+            pause_button_system.run_if(in_state(PauseState::Paused).and(in_state(MenuState::Disabled))),
+            pause_overlay_visibility.run_if(state_changed::<MenuState>.and(in_state(PauseState::Paused))),
+        )
+            .run_if(in_state(AppState::Game)),
+    )
+    .add_systems(OnEnter(PauseState::Paused), (pause_overlay_setup, pause_time))
+    .add_systems(OnExit(PauseState::Paused), (despawn_screen::<OnPauseOverlay>, unpause_time));
+}
+
+/// Pause key, start button of a gamepad or pause button on game screen switch pause on and off
+fn pause_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    gamepads: Query<&Gamepad>,
+    pause_state: Res<State<PauseState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+    pause_button_query: Query<&Interaction, (Changed<Interaction>, With<PauseButton>)>,
+) {
+    let pressed = key_bindings.just_pressed(Action::Pause, &keyboard_input)
+        || gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::Start))
+        || pause_button_query.iter().any(|interaction| *interaction == Interaction::Pressed);
+    if !pressed {
+        return;
+    }
+
+    next_pause_state.set(match pause_state.get() {
+        PauseState::Running => PauseState::Paused,
+        PauseState::Paused => PauseState::Running,
+    });
+}
+
+/// Virtual time stops, so movement timers and fixed updates wait with the rest of the game
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn unpause_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn pause_overlay_setup(mut commands: Commands) {
+    spawn_overlay(&mut commands, OnPauseOverlay, "Paused", |parent| {
+        for (action, text) in [
+            (PauseButtonAction::Resume, "Resume"),
+            (PauseButtonAction::Restart, "Restart"),
+            (PauseButtonAction::Settings, "Settings"),
+            (PauseButtonAction::MainMenu, "Main menu"),
+        ] {
+            spawn_overlay_button(parent, action, text);
+        }
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn pause_button_system(
+    interaction_query: Query<(&Interaction, &PauseButtonAction), (Changed<Interaction>, With<Button>)>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut new_game_messages: MessageWriter<NewGameMessage>,
+) {
+    for (interaction, pause_button_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match pause_button_action {
+                PauseButtonAction::Resume => pause_state.set(PauseState::Running),
+                PauseButtonAction::Restart => {
+                    // Read by the game once it runs again
+                    new_game_messages.write(NewGameMessage);
+                    pause_state.set(PauseState::Running);
+                }
+                PauseButtonAction::Settings => menu_state.set(MenuState::Settings),
+                PauseButtonAction::MainMenu => app_state.set(AppState::Menu),
+            }
+        }
+    }
+}
+
+/// Settings opened from pause are shown instead of the overlay
+fn pause_overlay_visibility(
+    menu_state: Res<State<MenuState>>,
+    mut overlay_query: Query<&mut Visibility, With<OnPauseOverlay>>,
+) {
+    for mut visibility in &mut overlay_query {
+        *visibility = if *menu_state.get() == MenuState::Disabled {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
@@ -0,0 +1,470 @@
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use crate::game::components::*;
+use crate::game::mode::{Endless, GameMode};
+use crate::game::replay::Replay;
+use crate::game::rules::{WinTarget, RULES_VERSION};
+use crate::game::states::*;
+use crate::game::systems::game_logic::produce_block_bundle;
+use crate::game::systems::history::restore_board;
+use crate::menu::AppState;
+
+/// Playback speeds, as multipliers of the normal move time
+pub const PLAYBACK_SPEEDS: [f32; 6] = [0.25, 0.5, 1., 2., 4., 8.];
+const NORMAL_SPEED_INDEX: usize = 2;
+/// Pause between moves at normal speed, in seconds
+const MOVE_INTERVAL: f32 = 0.5;
+
+/// Replay that can be watched from the main menu, last finished game or the one given on the command line
+#[derive(Resource, Default)]
+pub struct LoadedReplay(pub Option<Replay>);
+
+/// Whether the game being started plays back [LoadedReplay] instead of being played
+#[derive(Resource, Default)]
+pub struct WatchReplay(pub bool);
+
+/// Board at rest after a number of moves of the replay
+#[derive(Clone, PartialEq, Debug)]
+pub struct Frame {
+    pub values: Board<usize>,
+    pub score: u64,
+}
+
+/// State of replay being watched, exists only during playback
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    /// Frame for every position, first one is the board before the first move
+    pub frames: Vec<Frame>,
+    /// Number of moves played so far
+    pub position: usize,
+    pub playing: bool,
+    pub speed_index: usize,
+    /// Move time to go back to after playback
+    base_move_time: f32,
+    delay: Timer,
+}
+
+impl ReplayPlayback {
+    pub fn speed(&self) -> f32 {
+        PLAYBACK_SPEEDS[self.speed_index]
+    }
+
+    fn delay_timer(speed: f32) -> Timer {
+        Timer::from_seconds(MOVE_INTERVAL / speed, TimerMode::Once)
+    }
+}
+
+/// Actions of buttons on the playback panel
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PlaybackButtonAction {
+    StepBack,
+    PlayPause,
+    StepForward,
+    Slower,
+    Faster,
+    Exit,
+}
+
+/// Marker for the timeline, clicking or dragging on it seeks to that position
+#[derive(Component)]
+pub struct PlaybackScrubber;
+
+/// Marker for the filled part of the timeline
+#[derive(Component)]
+pub struct PlaybackProgress;
+
+/// Marker for text with position and speed of playback
+#[derive(Component)]
+pub struct PlaybackStatusText;
+
+/// Marker for text of the play and pause button
+#[derive(Component)]
+pub struct PlayPauseText;
+
+pub fn playback_plugin(app: &mut App) {
+    app.init_resource::<LoadedReplay>()
+        .init_resource::<WatchReplay>()
+        .add_systems(
+            Update,
+            (
+                playback_controls_system,
+                playback_system.run_if(in_state(GameState::Wait)),
+                update_playback_ui.run_if(resource_changed::<ReplayPlayback>),
+            )
+                .chain()
+                .run_if(resource_exists::<ReplayPlayback>)
+                .in_set(GameSet),
+        )
+        .add_systems(OnExit(AppState::Game), playback_cleanup.run_if(resource_exists::<ReplayPlayback>));
+}
+
+/// Run condition for systems that only make sense in games being played, not watched
+pub fn playing_replay(
+    watch_replay: Res<WatchReplay>,
+    loaded_replay: Res<LoadedReplay>,
+) -> bool {
+    watch_replay.0 && loaded_replay.0.is_some()
+}
+
+/// Frames of the whole replay, fails on moves that don't change the board and spawns on occupied tiles
+pub fn replay_frames(replay: &Replay) -> Result<Vec<Frame>, String> {
+    let mut values = Board::new(replay.width, replay.height);
+    for spawn in &replay.start {
+        values[spawn.col + spawn.row * replay.width] = Some(spawn.value);
+    }
+
+    let mut frames = vec![Frame { values, score: 0 }];
+    for (number, replay_move) in replay.moves.iter().enumerate() {
+        let Frame { values, score } = &frames[number];
+        let mut values = values.clone();
+        let outcome = values.apply_move(replay_move.direction);
+        if !outcome.changed {
+            return Err(format!("Move {} doesn't change the board", number + 1));
+        }
+        let score = score + outcome.score;
+
+        for spawn in &replay_move.spawns {
+            let index = spawn.col + spawn.row * replay.width;
+            if values[index].is_some() {
+                return Err(format!("Move {} spawns on occupied tile [{}, {}]", number + 1, spawn.col, spawn.row));
+            }
+            values[index] = Some(spawn.value);
+        }
+
+        frames.push(Frame { values, score });
+    }
+
+    Ok(frames)
+}
+
+/// Sets up the board from the start of [LoadedReplay] instead of a new board
+#[allow(clippy::too_many_arguments)]
+pub fn playback_setup(
+    mut commands: Commands,
+    loaded_replay: Res<LoadedReplay>,
+    mut layout: ResMut<BoardLayout>,
+    mut board_state_resource: ResMut<BoardStateResource>,
+    mut game_seed: ResMut<GameSeed>,
+    mut game_mode: ResMut<GameMode>,
+    mut win_target: ResMut<WinTarget>,
+    mut endless: ResMut<Endless>,
+    game_params: Res<GameParams>,
+) {
+    let Some(replay) = &loaded_replay.0 else {
+        panic!("Playback should only run with loaded replay")
+    };
+    let frames = replay_frames(replay).expect("Loaded replay should be checked");
+    if replay.rules_version != RULES_VERSION {
+        warn!("Replay was recorded with rules version {}, it may not play out the same", replay.rules_version);
+    }
+
+    *layout = BoardLayout::new(replay.width, replay.height);
+    let board = &mut board_state_resource.0;
+    *board = Board::new(replay.width, replay.height);
+    for (index, value) in frames[0].values.iter().enumerate() {
+        board[index] = value.map(|value| {
+            commands
+                .spawn(produce_block_bundle(index % replay.width, index / replay.width, value, &layout))
+                .id()
+        });
+    }
+
+    game_seed.0 = replay.seed;
+    *game_mode = replay.mode;
+    *win_target = replay.target;
+    // Reaching the target doesn't stop playback
+    endless.0 = true;
+
+    commands.insert_resource(ReplayPlayback {
+        replay: replay.clone(),
+        frames,
+        position: 0,
+        playing: true,
+        speed_index: NORMAL_SPEED_INDEX,
+        base_move_time: game_params.move_time,
+        delay: ReplayPlayback::delay_timer(PLAYBACK_SPEEDS[NORMAL_SPEED_INDEX]),
+    });
+}
+
+fn playback_cleanup(
+    mut commands: Commands,
+    playback: Res<ReplayPlayback>,
+    mut game_params: ResMut<GameParams>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    game_params.move_time = playback.base_move_time;
+    game_state.set(GameState::Wait);
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+/// Plays the next move through the usual pipeline, with spawns forced from the replay
+fn playback_system(
+    time: Res<Time>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut forced_spawn: ResMut<ForcedSpawn>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut direction_messages: MessageWriter<DirectionMessage>,
+) {
+    if !playback.playing {
+        return;
+    }
+    if !playback.delay.tick(time.delta()).is_finished() {
+        return;
+    }
+
+    let Some(replay_move) = playback.replay.moves.get(playback.position) else {
+        playback.playing = false;
+        return;
+    };
+    forced_spawn.0 = Some(replay_move.spawns.clone());
+    direction_messages.write(DirectionMessage(replay_move.direction));
+    game_state.set(GameState::Process);
+
+    let speed = playback.speed();
+    playback.position += 1;
+    playback.delay = ReplayPlayback::delay_timer(speed);
+}
+
+/// Handles buttons, timeline and keyboard: space plays and pauses, left and right step, down and up change speed
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn playback_controls_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    interaction_query: Query<(&Interaction, &PlaybackButtonAction), (Changed<Interaction>, With<Button>)>,
+    scrubber_query: Query<(&Interaction, &RelativeCursorPosition), With<PlaybackScrubber>>,
+    state: Res<State<GameState>>,
+    layout: Res<BoardLayout>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut game_params: ResMut<GameParams>,
+    mut board_state_resource: ResMut<BoardStateResource>,
+    mut score: ResMut<Score>,
+    mut moves: ResMut<Moves>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut app_state: ResMut<NextState<AppState>>,
+    block_query: Query<&Value, With<Block>>,
+) {
+    let mut actions: Vec<PlaybackButtonAction> = interaction_query
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, action)| *action)
+        .collect();
+    for (key, action) in [
+        (KeyCode::Space, PlaybackButtonAction::PlayPause),
+        (KeyCode::ArrowLeft, PlaybackButtonAction::StepBack),
+        (KeyCode::ArrowRight, PlaybackButtonAction::StepForward),
+        (KeyCode::ArrowDown, PlaybackButtonAction::Slower),
+        (KeyCode::ArrowUp, PlaybackButtonAction::Faster),
+    ] {
+        if keyboard_input.just_pressed(key) {
+            actions.push(action);
+        }
+    }
+
+    let last_position = playback.replay.moves.len();
+    let mut seek = None;
+    for action in actions {
+        match action {
+            PlaybackButtonAction::PlayPause => {
+                // Playing from the end starts over
+                if !playback.playing && playback.position == last_position {
+                    seek = Some(0);
+                }
+                playback.playing = !playback.playing;
+            }
+            PlaybackButtonAction::StepBack => {
+                playback.playing = false;
+                seek = Some(playback.position.saturating_sub(1));
+            }
+            PlaybackButtonAction::StepForward => {
+                playback.playing = false;
+                seek = Some((playback.position + 1).min(last_position));
+            }
+            PlaybackButtonAction::Slower => playback.speed_index = playback.speed_index.saturating_sub(1),
+            PlaybackButtonAction::Faster => {
+                playback.speed_index = (playback.speed_index + 1).min(PLAYBACK_SPEEDS.len() - 1)
+            }
+            PlaybackButtonAction::Exit => app_state.set(AppState::Menu),
+        }
+    }
+
+    // Held press on the timeline keeps seeking while dragging
+    for (interaction, cursor_position) in &scrubber_query {
+        if let (Interaction::Pressed, Some(normalized)) = (interaction, cursor_position.normalized) {
+            let fraction = (normalized.x + 0.5).clamp(0., 1.);
+            seek = Some((fraction * last_position as f32).round() as usize);
+        }
+    }
+
+    let move_time = playback.base_move_time / playback.speed();
+    if game_params.move_time != move_time {
+        game_params.move_time = move_time;
+    }
+
+    // Board can only be rebuilt at rest
+    let Some(position) = seek else {
+        return;
+    };
+    if !matches!(state.get(), GameState::Wait | GameState::Lose) || position == playback.position {
+        return;
+    }
+
+    let frame = &playback.frames[position];
+    restore_board(&mut commands, &layout, &mut board_state_resource.0, &frame.values, &block_query);
+    score.0 = frame.score;
+    moves.0 = position as u64;
+    playback.position = position;
+    playback.delay = ReplayPlayback::delay_timer(playback.speed());
+    game_state.set(GameState::Wait);
+}
+
+pub fn playback_ui_setup(mut commands: Commands) {
+    let button_node = Node {
+        width: Val::Px(90.),
+        height: Val::Px(65.),
+        margin: UiRect::all(Val::Px(10.)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text = (
+        TextFont {
+            font_size: 33.,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+    );
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(0.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(10.)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.7)),
+            OnGameScreen,
+        ))
+        .with_children(|parent| {
+            // Timeline
+            parent
+                .spawn((
+                    Button,
+                    Node {
+                        width: Val::Px(800.),
+                        height: Val::Px(24.),
+                        margin: UiRect::all(Val::Px(10.)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+                    RelativeCursorPosition::default(),
+                    PlaybackScrubber,
+                ))
+                .with_child((
+                    Node {
+                        width: Val::Percent(0.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.35, 0.75, 0.35)),
+                    PlaybackProgress,
+                ));
+
+            parent
+                .spawn(Node {
+                    align_items: AlignItems::Center,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (action, text) in [
+                        (PlaybackButtonAction::StepBack, "<"),
+                        (PlaybackButtonAction::PlayPause, "Pause"),
+                        (PlaybackButtonAction::StepForward, ">"),
+                        (PlaybackButtonAction::Slower, "-"),
+                        (PlaybackButtonAction::Faster, "+"),
+                        (PlaybackButtonAction::Exit, "Exit"),
+                    ] {
+                        let mut text_entity = parent.spawn((
+                            Button,
+                            Node {
+                                width: Val::Px(if action == PlaybackButtonAction::PlayPause { 150. } else { 90. }),
+                                ..button_node.clone()
+                            },
+                            BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+                            action,
+                        ));
+                        if action == PlaybackButtonAction::PlayPause {
+                            text_entity.with_child((Text::new(text), button_text.clone(), PlayPauseText));
+                        } else {
+                            text_entity.with_child((Text::new(text), button_text.clone()));
+                        }
+                    }
+                    parent.spawn((
+                        Text::new(""),
+                        button_text.clone(),
+                        Node {
+                            margin: UiRect::horizontal(Val::Px(20.)),
+                            ..default()
+                        },
+                        PlaybackStatusText,
+                    ));
+                });
+        });
+}
+
+fn update_playback_ui(
+    playback: Res<ReplayPlayback>,
+    mut progress_query: Query<&mut Node, With<PlaybackProgress>>,
+    mut status_query: Query<&mut Text, (With<PlaybackStatusText>, Without<PlayPauseText>)>,
+    mut play_pause_query: Query<&mut Text, (With<PlayPauseText>, Without<PlaybackStatusText>)>,
+) {
+    let last_position = playback.replay.moves.len();
+    let progress = if last_position == 0 { 100. } else { playback.position as f32 * 100. / last_position as f32 };
+
+    for mut node in &mut progress_query {
+        node.width = Val::Percent(progress);
+    }
+    for mut text in &mut status_query {
+        text.0 = format!("{} / {}  {}x", playback.position, last_position, playback.speed());
+    }
+    for mut text in &mut play_pause_query {
+        text.0 = if playback.playing { "Pause" } else { "Play" }.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_frames_test() {
+        let replay = include_str!("../../tests/fixtures/short_game.replay").parse::<Replay>().unwrap();
+        let frames = replay_frames(&replay).unwrap();
+
+        assert_eq!(frames.len(), 5);
+        let last = frames.last().unwrap();
+        let mut expected = Board::new(4, 4);
+        expected[0] = Some(3);
+        expected[1] = Some(2);
+        expected[10] = Some(1);
+        assert_eq!(last.values, expected);
+        assert_eq!(last.score, 16);
+    }
+
+    #[test]
+    fn invalid_replay_frames_test() {
+        let mut replay = include_str!("../../tests/fixtures/short_game.replay").parse::<Replay>().unwrap();
+        // Second move spawns where the merged tile is
+        replay.moves[1].spawns = vec![TileSpawn { col: 0, row: 0, value: 1 }];
+        assert!(replay_frames(&replay).is_err());
+
+        // Spawn lines the first column up, moving left again doesn't change the board
+        replay.moves[0].spawns = vec![TileSpawn { col: 0, row: 1, value: 1 }];
+        replay.moves[1] = replay.moves[0].clone();
+        assert!(replay_frames(&replay).is_err());
+    }
+}
@@ -0,0 +1,399 @@
+use std::fmt;
+use std::str::FromStr;
+use bevy::prelude::*;
+use crate::game::components::*;
+use crate::game::mode::GameMode;
+use crate::game::playback::{playing_replay, replay_frames, LoadedReplay};
+use crate::game::rules::{WinTarget, RULES_VERSION};
+use crate::game::states::*;
+use crate::game::stats::Statistics;
+use crate::game::systems::game_logic::values_board;
+use crate::storage::StorageResource;
+
+/// Version of the replay format, replays with other version can't be read
+pub const REPLAY_VERSION: u32 = 1;
+const REPLAY_HEADER: &str = "2048-replay";
+
+/// Recorded game, enough to play it again move by move
+///
+/// Replays are stored as plain text, one entry per line, so they can be diffed and kept as fixtures.
+/// Empty lines and lines starting with `#` are ignored.
+///
+/// ```text
+/// 2048-replay 1
+/// seed 42
+/// size 4x4
+/// rules 1
+/// mode Classic
+/// target 2048
+/// tile 1 0 2
+/// tile 3 2 2
+/// move left 2 1 2
+/// move up 3 3 4
+/// ```
+///
+/// - `2048-replay` with the version of the format, always the first line
+/// - `seed` of random numbers the game was started with
+/// - `size` of the board as width and height
+/// - `rules` version the game was played with, see [RULES_VERSION]
+/// - `mode` and win `target`, `target None` to play until the board is stuck
+/// - `tile` on the board before the first move, as column, row and tile value
+/// - `move` direction (`left`, `right`, `up` or `down`), then column, row and tile value of the tile spawned after it,
+///   repeated for every tile when the spawner of the mode spawns more than one
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub width: usize,
+    pub height: usize,
+    pub rules_version: u32,
+    pub mode: GameMode,
+    pub target: WinTarget,
+    /// Tiles on the board before the first move
+    pub start: Vec<TileSpawn>,
+    pub moves: Vec<ReplayMove>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            width: 4,
+            height: 4,
+            rules_version: RULES_VERSION,
+            mode: GameMode::default(),
+            target: WinTarget::default(),
+            start: Vec::new(),
+            moves: Vec::new(),
+        }
+    }
+}
+
+/// Move with tiles spawned after it
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ReplayMove {
+    pub direction: Direction,
+    pub spawns: Vec<TileSpawn>,
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Left => "left",
+        Direction::Right => "right",
+        Direction::Up => "up",
+        Direction::Down => "down",
+    }
+}
+
+fn parse_direction(value: &str) -> Result<Direction, String> {
+    match value {
+        "left" => Ok(Direction::Left),
+        "right" => Ok(Direction::Right),
+        "up" => Ok(Direction::Up),
+        "down" => Ok(Direction::Down),
+        _ => Err(format!("unknown direction {value}")),
+    }
+}
+
+/// Tile value like `2` or `2048` as power of two
+fn parse_tile_value(value: &str) -> Result<usize, String> {
+    match value.parse::<u64>() {
+        Ok(tile) if tile >= 2 && tile.is_power_of_two() => Ok(tile.trailing_zeros() as usize),
+        _ => Err(format!("invalid tile value {value}")),
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid number {value}"))
+}
+
+/// Width and height like `4x4`
+fn parse_size(value: &str) -> Result<(usize, usize), String> {
+    let Some((width, height)) = value.split_once('x') else {
+        return Err(format!("invalid size {value}"));
+    };
+
+    Ok((parse_number(width)?, parse_number(height)?))
+}
+
+/// Column, row and tile value of a spawned tile
+fn parse_spawn(values: &[&str]) -> Result<TileSpawn, String> {
+    let [col, row, value] = values else {
+        return Err("expected column, row and tile value".to_string());
+    };
+
+    Ok(TileSpawn {
+        col: parse_number(col)?,
+        row: parse_number(row)?,
+        value: parse_tile_value(value)?,
+    })
+}
+
+/// One or more spawned tiles, three values each
+fn parse_spawns(values: &[&str]) -> Result<Vec<TileSpawn>, String> {
+    if values.is_empty() || !values.len().is_multiple_of(3) {
+        return Err("expected column, row and tile value of every spawned tile".to_string());
+    }
+
+    values.chunks(3).map(parse_spawn).collect()
+}
+
+fn write_spawn(f: &mut fmt::Formatter, spawn: &TileSpawn) -> fmt::Result {
+    write!(f, "{} {} {}", spawn.col, spawn.row, 1u64 << spawn.value)
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{REPLAY_HEADER} {REPLAY_VERSION}")?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "size {}x{}", self.width, self.height)?;
+        writeln!(f, "rules {}", self.rules_version)?;
+        writeln!(f, "mode {}", self.mode.name())?;
+        writeln!(f, "target {}", self.target.name())?;
+        for spawn in &self.start {
+            write!(f, "tile ")?;
+            write_spawn(f, spawn)?;
+            writeln!(f)?;
+        }
+        for replay_move in &self.moves {
+            write!(f, "move {}", direction_name(replay_move.direction))?;
+            for spawn in &replay_move.spawns {
+                write!(f, " ")?;
+                write_spawn(f, spawn)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = String;
+
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        match lines.next().map(|(_, line)| line.split_once(' ')) {
+            Some(Some((REPLAY_HEADER, version))) if version == REPLAY_VERSION.to_string() => {}
+            Some(Some((REPLAY_HEADER, version))) => return Err(format!("Replay version {version} is not supported")),
+            _ => return Err(format!("Replay should start with {REPLAY_HEADER}")),
+        }
+
+        let mut replay = Replay::default();
+        let (mut seed, mut size, mut mode, mut target) = (None, None, None, None);
+        for (number, line) in lines {
+            let values: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match values.as_slice() {
+                ["seed", value] => parse_number(value).map(|value| seed = Some(value)),
+                ["size", value] => parse_size(value).map(|value| size = Some(value)),
+                ["rules", version] => parse_number(version).map(|version| replay.rules_version = version),
+                ["mode", name] => GameMode::ALL
+                    .into_iter()
+                    .find(|game_mode| game_mode.name() == *name)
+                    .map(|game_mode| mode = Some(game_mode))
+                    .ok_or(format!("unknown mode {name}")),
+                ["target", "None"] => {
+                    target = Some(WinTarget(None));
+                    Ok(())
+                }
+                ["target", value] => parse_tile_value(value).map(|value| target = Some(WinTarget(Some(value)))),
+                ["tile", spawn @ ..] => parse_spawn(spawn).map(|spawn| replay.start.push(spawn)),
+                ["move", direction, spawns @ ..] => parse_direction(direction).and_then(|direction| {
+                    let spawns = parse_spawns(spawns)?;
+                    replay.moves.push(ReplayMove { direction, spawns });
+                    Ok(())
+                }),
+                _ => Err("unknown entry".to_string()),
+            };
+            parsed.map_err(|e| format!("Line {number}: {e}"))?;
+        }
+
+        let missing = |name: &str| format!("Replay has no {name}");
+        replay.seed = seed.ok_or_else(|| missing("seed"))?;
+        (replay.width, replay.height) = size.ok_or_else(|| missing("size"))?;
+        replay.mode = mode.ok_or_else(|| missing("mode"))?;
+        replay.target = target.ok_or_else(|| missing("target"))?;
+
+        let outside = replay
+            .start
+            .iter()
+            .chain(replay.moves.iter().flat_map(|replay_move| &replay_move.spawns))
+            .find(|spawn| spawn.col >= replay.width || spawn.row >= replay.height);
+        if let Some(spawn) = outside {
+            return Err(format!("Tile [{}, {}] is outside of the board", spawn.col, spawn.row));
+        }
+
+        Ok(replay)
+    }
+}
+
+/// Replay of the current game, recorded move by move
+#[derive(Resource, Default)]
+pub struct ReplayRecording {
+    pub replay: Replay,
+    /// Direction of the move being processed, recorded once its spawns are known
+    pending: Option<Direction>,
+}
+
+pub fn replay_plugin(app: &mut App) {
+    app.init_resource::<ReplayRecording>()
+        .add_systems(Update, record_direction_system.after(InputSet).in_set(GameSet))
+        .add_systems(OnEnter(GameState::Win), save_replay_system.run_if(not(playing_replay)))
+        .add_systems(OnEnter(GameState::Lose), save_replay_system.run_if(not(playing_replay)));
+}
+
+/// Starts a new recording from the board as it is set up, continued games start from the resumed board
+pub fn start_recording_system(
+    game_seed: Res<GameSeed>,
+    game_mode: Res<GameMode>,
+    win_target: Res<WinTarget>,
+    board_state_resource: Res<BoardStateResource>,
+    block_query: Query<&Value, With<Block>>,
+    mut recording: ResMut<ReplayRecording>,
+) {
+    let values = values_board(&board_state_resource.0, &block_query);
+    let start = values
+        .iter()
+        .enumerate()
+        .filter_map(|(index, value)| {
+            value.map(|value| TileSpawn { col: index % values.width, row: index / values.width, value })
+        })
+        .collect();
+
+    *recording = ReplayRecording {
+        replay: Replay {
+            seed: game_seed.0,
+            width: values.width,
+            height: values.height,
+            rules_version: RULES_VERSION,
+            mode: *game_mode,
+            target: *win_target,
+            start,
+            moves: Vec::new(),
+        },
+        pending: None,
+    };
+}
+
+fn record_direction_system(
+    mut direction_messages: MessageReader<DirectionMessage>,
+    mut recording: ResMut<ReplayRecording>,
+) {
+    // Same message as the one processed for the move
+    if let Some(message) = direction_messages.read().last() {
+        recording.pending = Some(message.0);
+    }
+}
+
+/// Records the move with its spawns, moves undone before it are dropped
+pub fn record_move_system(
+    mut spawn_messages: MessageReader<SpawnMessage>,
+    moves: Res<Moves>,
+    mut recording: ResMut<ReplayRecording>,
+) {
+    let spawns: Vec<TileSpawn> = spawn_messages.read().map(|message| message.0).collect();
+    if spawns.is_empty() {
+        return;
+    }
+    let Some(direction) = recording.pending.take() else {
+        warn!("Tile spawned without a move to record");
+        return;
+    };
+
+    let replay = &mut recording.replay;
+    replay.moves.truncate(moves.0 as usize);
+    replay.moves.push(ReplayMove { direction, spawns });
+}
+
+/// Replays are kept as `replays/<game number>-<seed>.replay`, game ended again after undo overwrites its replay.
+/// Saved replay can be watched right away from the main menu.
+fn save_replay_system(
+    storage: Res<StorageResource>,
+    statistics: Res<Statistics>,
+    moves: Res<Moves>,
+    mut recording: ResMut<ReplayRecording>,
+    mut loaded_replay: ResMut<LoadedReplay>,
+) {
+    let replay = &mut recording.replay;
+    replay.moves.truncate(moves.0 as usize);
+
+    let key = format!("replays/{:05}-{}.replay", statistics.games_played, replay.seed);
+    match storage.write(&key, &replay.to_string()) {
+        Ok(()) => info!("Replay saved as {}", key),
+        Err(e) => warn!("Replay can't be saved: {}", e),
+    }
+
+    match replay_frames(replay) {
+        Ok(_) => loaded_replay.0 = Some(replay.clone()),
+        Err(e) => warn!("Recorded replay can't be played back: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_round_trip_test() {
+        let replay = Replay {
+            seed: u64::MAX,
+            width: 5,
+            height: 3,
+            rules_version: RULES_VERSION,
+            mode: GameMode::Hardcore,
+            target: WinTarget(None),
+            start: vec![TileSpawn { col: 4, row: 2, value: 1 }, TileSpawn { col: 0, row: 0, value: 17 }],
+            moves: vec![
+                ReplayMove { direction: Direction::Down, spawns: vec![TileSpawn { col: 1, row: 0, value: 2 }] },
+                ReplayMove { direction: Direction::Left, spawns: vec![TileSpawn { col: 4, row: 1, value: 1 }] },
+                ReplayMove {
+                    direction: Direction::Up,
+                    spawns: vec![TileSpawn { col: 2, row: 2, value: 1 }, TileSpawn { col: 3, row: 2, value: 2 }],
+                },
+            ],
+        };
+
+        let contents = replay.to_string();
+        assert!(contents.contains("tile 0 0 131072\n"));
+        assert!(contents.contains("move down 1 0 4\n"));
+        assert!(contents.contains("move up 2 2 2 3 2 4\n"));
+        assert_eq!(contents.parse::<Replay>(), Ok(replay));
+    }
+
+    #[test]
+    fn replay_fixture_test() {
+        let contents = include_str!("../../tests/fixtures/short_game.replay");
+        let replay = contents.parse::<Replay>().unwrap();
+
+        assert_eq!(replay.seed, 7);
+        assert_eq!((replay.width, replay.height), (4, 4));
+        assert_eq!(replay.target, WinTarget(Some(11)));
+        assert_eq!(replay.start.len(), 2);
+        assert_eq!(replay.moves.len(), 4);
+        assert_eq!(
+            replay.moves[1],
+            ReplayMove { direction: Direction::Up, spawns: vec![TileSpawn { col: 3, row: 3, value: 2 }] }
+        );
+    }
+
+    #[test]
+    fn invalid_replay_test() {
+        let header = "2048-replay 1\nseed 1\nsize 4x4\nmode Classic\ntarget 2048\n";
+
+        assert!("seed 1\n".parse::<Replay>().is_err());
+        assert!("2048-replay 2\nseed 1\n".parse::<Replay>().is_err());
+        assert!(header.replace("seed 1\n", "").parse::<Replay>().is_err());
+        assert!(format!("{header}tile 0 0 3\n").parse::<Replay>().is_err());
+        assert!(format!("{header}tile 4 0 2\n").parse::<Replay>().is_err());
+        assert!(format!("{header}move sideways 0 0 2\n").parse::<Replay>().is_err());
+        assert!(format!("{header}move left 0 0\n").parse::<Replay>().is_err());
+        assert!(format!("{header}move left\n").parse::<Replay>().is_err());
+        assert!(format!("{header}move left 0 0 2 1\n").parse::<Replay>().is_err());
+        assert!(format!("{header}move left 0 0 2 4 0 2\n").parse::<Replay>().is_err());
+        assert!(format!("{header}\n# comment\nmove left 0 0 2\n").parse::<Replay>().is_ok());
+    }
+}
@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::game::components::Board;
+
+/// Version of the rules, bumped when a change makes recorded games play out differently
+pub const RULES_VERSION: u32 = 1;
+
+/// Result of evaluating the board after a move
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Outcome {
+    Win,
+    Lose,
+    Continue,
+}
+
+/// End of game conditions, evaluated on values of the board after every move
+pub trait Rules: Send + Sync {
+    fn evaluate(&self, board: &Board<usize>) -> Outcome;
+}
+
+/// Rules of the current game, set up when game starts
+#[derive(Resource, Deref)]
+pub struct RulesResource(pub Box<dyn Rules>);
+
+impl Default for RulesResource {
+    fn default() -> Self {
+        Self(Box::new(ClassicRules::new(WinTarget::default())))
+    }
+}
+
+/// Value of the tile that wins the game, as power of two, `None` to play until the board is stuck
+#[derive(Resource, Component, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct WinTarget(pub Option<usize>);
+
+impl Default for WinTarget {
+    fn default() -> Self {
+        Self(Some(11))
+    }
+}
+
+impl WinTarget {
+    pub const ALL: [WinTarget; 4] = [WinTarget(Some(9)), WinTarget(Some(11)), WinTarget(Some(12)), WinTarget(None)];
+
+    pub fn name(&self) -> String {
+        match self.0 {
+            Some(value) => (1u64 << value).to_string(),
+            None => "None".to_string(),
+        }
+    }
+}
+
+/// Win on reaching the target tile, lose when no move is left
+pub struct ClassicRules {
+    target: WinTarget,
+}
+
+impl ClassicRules {
+    pub fn new(target: WinTarget) -> Self {
+        Self { target }
+    }
+}
+
+impl Rules for ClassicRules {
+    fn evaluate(&self, board: &Board<usize>) -> Outcome {
+        // Whole board is checked for the target, before the stuck board
+        match self.target.0 {
+            Some(target) if board.iter().flatten().any(|&value| value >= target) => Outcome::Win,
+            _ if board.is_terminal() => Outcome::Lose,
+            _ => Outcome::Continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn win_after_mergeable_tiles_test() {
+        // First tiles can be merged, target is at the very end
+        let board = Board::from_values(4, 4, &[1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 11]);
+
+        assert_eq!(ClassicRules::new(WinTarget::default()).evaluate(&board), Outcome::Win);
+    }
+
+    #[test]
+    fn lose_on_stuck_board_test() {
+        let board = Board::from_values(4, 4, &[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 1]);
+
+        assert_eq!(ClassicRules::new(WinTarget::default()).evaluate(&board), Outcome::Lose);
+        assert_eq!(ClassicRules::new(WinTarget(None)).evaluate(&board), Outcome::Lose);
+    }
+
+    #[test]
+    fn win_on_stuck_board_with_target_test() {
+        let board = Board::from_values(4, 4, &[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 11]);
+
+        assert_eq!(ClassicRules::new(WinTarget(Some(11))).evaluate(&board), Outcome::Win);
+        assert_eq!(ClassicRules::new(WinTarget(Some(9))).evaluate(&board), Outcome::Win);
+        assert_eq!(ClassicRules::new(WinTarget(None)).evaluate(&board), Outcome::Lose);
+    }
+
+    #[test]
+    fn continue_with_moves_left_test() {
+        let empty = Board::from_values(4, 4, &[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 0]);
+        let merge_down = Board::from_values(4, 4, &[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 1, 3, 4, 3]);
+        let merge_right = Board::from_values(4, 4, &[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 3, 3, 1]);
+
+        let rules = ClassicRules::new(WinTarget::default());
+        assert_eq!(rules.evaluate(&empty), Outcome::Continue);
+        assert_eq!(rules.evaluate(&merge_down), Outcome::Continue);
+        assert_eq!(rules.evaluate(&merge_right), Outcome::Continue);
+    }
+
+    #[test]
+    fn target_test() {
+        let board = Board::from_values(3, 3, &[9, 0, 0, 0, 11, 0, 0, 0, 0]);
+
+        assert_eq!(ClassicRules::new(WinTarget(Some(9))).evaluate(&board), Outcome::Win);
+        assert_eq!(ClassicRules::new(WinTarget(Some(11))).evaluate(&board), Outcome::Win);
+        assert_eq!(ClassicRules::new(WinTarget(Some(12))).evaluate(&board), Outcome::Continue);
+        assert_eq!(ClassicRules::new(WinTarget(None)).evaluate(&board), Outcome::Continue);
+    }
+
+    #[test]
+    fn rectangular_board_test() {
+        // Equal values wrap around the row end, which is not a neighbour
+        let stuck = Board::from_values(3, 2, &[1, 2, 3, 3, 1, 2]);
+        let column_merge = Board::from_values(2, 3, &[1, 2, 3, 2, 4, 5]);
+
+        let rules = ClassicRules::new(WinTarget::default());
+        assert_eq!(rules.evaluate(&stuck), Outcome::Lose);
+        assert_eq!(rules.evaluate(&column_merge), Outcome::Continue);
+    }
+}
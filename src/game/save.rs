@@ -0,0 +1,341 @@
+use bevy::prelude::*;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use crate::game::components::*;
+use crate::game::mode::{Endless, GameMode};
+use crate::game::playback::playing_replay;
+use crate::game::rules::WinTarget;
+use crate::game::states::*;
+use crate::game::systems::game_logic::{produce_block_bundle, values_board};
+use crate::menu::AppState;
+use crate::storage::StorageResource;
+use crate::SharedRand;
+
+const SAVED_GAME_KEY: &str = "saved_game.ron";
+/// Version of [SavedGame], saves with other version are ignored
+const SAVED_GAME_VERSION: u32 = 1;
+
+/// Game in progress, saved after every move to be continued later
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SavedGame {
+    version: u32,
+    width: usize,
+    height: usize,
+    /// Values of blocks with `col + row * width` as index
+    values: Vec<Option<usize>>,
+    score: u64,
+    moves: u64,
+    #[serde(default)]
+    seed: u64,
+    mode: GameMode,
+    #[serde(default)]
+    target: WinTarget,
+    #[serde(default)]
+    endless: Endless,
+    rng: RngState,
+}
+
+/// State of [ChaCha8Rng], enough to continue the same stream of numbers
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+struct RngState {
+    seed: [u8; 32],
+    stream: u64,
+    word_pos: u128,
+}
+
+impl From<&ChaCha8Rng> for RngState {
+    fn from(rng: &ChaCha8Rng) -> Self {
+        Self {
+            seed: rng.get_seed(),
+            stream: rng.get_stream(),
+            word_pos: rng.get_word_pos(),
+        }
+    }
+}
+
+impl From<&RngState> for ChaCha8Rng {
+    fn from(state: &RngState) -> Self {
+        let mut rng = ChaCha8Rng::from_seed(state.seed);
+        rng.set_stream(state.stream);
+        rng.set_word_pos(state.word_pos);
+        rng
+    }
+}
+
+/// Last saved game, loaded on startup and kept in sync with storage
+#[derive(Resource, Default)]
+pub struct SavedGameResource(pub Option<SavedGame>);
+
+/// Whether the game being started continues [SavedGameResource] instead of starting anew
+#[derive(Resource, Default)]
+pub struct ContinueGame(pub bool);
+
+pub fn save_plugin(app: &mut App) {
+    app.init_resource::<SavedGameResource>()
+        .init_resource::<ContinueGame>()
+        .add_systems(Startup, load_saved_game)
+        // New game abandons the saved one
+        .add_systems(
+            OnEnter(AppState::Game),
+            remove_saved_game.run_if(not(continuing_game).and(not(playing_replay))),
+        )
+        .add_systems(Update, remove_saved_game.run_if(on_message::<NewGameMessage>))
+        .add_systems(OnEnter(GameState::Lose), remove_saved_game.run_if(not(playing_replay)))
+        // Won game would be won again on the next move, unless it keeps going
+        .add_systems(
+            OnEnter(GameState::Win),
+            remove_saved_game.run_if(not(playing_replay).and(|endless: Res<Endless>| !endless.0)),
+        );
+}
+
+/// Run condition for systems that set up continued game
+pub fn continuing_game(
+    continue_game: Res<ContinueGame>,
+    saved_game: Res<SavedGameResource>,
+) -> bool {
+    continue_game.0 && saved_game.0.is_some()
+}
+
+fn load_saved_game(
+    storage: Res<StorageResource>,
+    mut saved_game: ResMut<SavedGameResource>,
+) {
+    let Some(contents) = storage.read(SAVED_GAME_KEY) else {
+        debug!("No saved game");
+        return;
+    };
+
+    match ron::from_str::<SavedGame>(&contents) {
+        Ok(game) if game.version != SAVED_GAME_VERSION => {
+            warn!("Saved game version {} is not supported, ignoring it", game.version)
+        }
+        Ok(game) if game.values.len() != game.width * game.height => {
+            warn!("Saved game board doesn't match its size, ignoring it")
+        }
+        Ok(game) => saved_game.0 = Some(game),
+        Err(e) => warn!("Saved game can't be read, ignoring it: {}", e),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn save_game_system(
+    storage: Res<StorageResource>,
+    board_state_resource: Res<BoardStateResource>,
+    block_query: Query<&Value, With<Block>>,
+    score: Res<Score>,
+    moves: Res<Moves>,
+    game_seed: Res<GameSeed>,
+    game_mode: Res<GameMode>,
+    win_target: Res<WinTarget>,
+    endless: Res<Endless>,
+    shared_rand: Res<SharedRand>,
+    mut saved_game: ResMut<SavedGameResource>,
+) {
+    let values = values_board(&board_state_resource.0, &block_query);
+    let game = SavedGame {
+        version: SAVED_GAME_VERSION,
+        width: values.width,
+        height: values.height,
+        values: values.cells,
+        score: score.0,
+        moves: moves.0,
+        seed: game_seed.0,
+        mode: *game_mode,
+        target: *win_target,
+        endless: *endless,
+        rng: RngState::from(&shared_rand.0),
+    };
+
+    match ron::ser::to_string_pretty(&game, ron::ser::PrettyConfig::default()) {
+        Ok(contents) => {
+            if let Err(e) = storage.write(SAVED_GAME_KEY, &contents) {
+                warn!("Game can't be saved: {}", e);
+            }
+        }
+        Err(e) => warn!("Game can't be serialized: {}", e),
+    }
+
+    saved_game.0 = Some(game);
+}
+
+/// Saved game is over, with a new game started, a loss or a win
+fn remove_saved_game(
+    storage: Res<StorageResource>,
+    mut saved_game: ResMut<SavedGameResource>,
+) {
+    saved_game.0 = None;
+    if let Err(e) = storage.remove(SAVED_GAME_KEY) {
+        warn!("Saved game can't be removed: {}", e);
+    }
+}
+
+/// Rebuilds blocks from [SavedGameResource] instead of setting up a new board, random numbers continue the same stream
+#[allow(clippy::too_many_arguments)]
+pub fn resume_game_setup(
+    mut commands: Commands,
+    saved_game: Res<SavedGameResource>,
+    mut layout: ResMut<BoardLayout>,
+    mut board_state_resource: ResMut<BoardStateResource>,
+    mut score: ResMut<Score>,
+    mut moves: ResMut<Moves>,
+    mut game_seed: ResMut<GameSeed>,
+    mut game_mode: ResMut<GameMode>,
+    mut win_target: ResMut<WinTarget>,
+    mut endless: ResMut<Endless>,
+    mut shared_rand: ResMut<SharedRand>,
+) {
+    let Some(game) = &saved_game.0 else {
+        panic!("Resume should only run with saved game")
+    };
+
+    *layout = BoardLayout::new(game.width, game.height);
+    let board = &mut board_state_resource.0;
+    *board = Board::new(game.width, game.height);
+    for (index, value) in game.values.iter().enumerate() {
+        board[index] = value.map(|value| {
+            commands
+                .spawn(produce_block_bundle(index % game.width, index / game.width, value, &layout))
+                .id()
+        });
+    }
+
+    score.0 = game.score;
+    moves.0 = game.moves;
+    game_seed.0 = game.seed;
+    *game_mode = game.mode;
+    *win_target = game.target;
+    *endless = game.endless;
+    shared_rand.0 = ChaCha8Rng::from(&game.rng);
+    trace!("Resumed {}", board);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use crate::game::game_setup_systems;
+    use crate::game::playback::{LoadedReplay, WatchReplay};
+    use crate::game::replay::ReplayRecording;
+    use crate::game::spawner::{EvilSpawner, Spawner, SpawnerResource};
+    use crate::storage::{MemoryStorage, Storage};
+    use bevy::state::app::StatesPlugin;
+
+    #[test]
+    fn saved_game_round_trip_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2048);
+        rng.set_stream(7);
+        let _: u64 = rng.random();
+
+        let game = SavedGame {
+            version: SAVED_GAME_VERSION,
+            width: 3,
+            height: 2,
+            values: vec![Some(1), None, Some(11), None, None, Some(2)],
+            score: 2068,
+            moves: 42,
+            seed: 2048,
+            mode: GameMode::Relaxed,
+            target: WinTarget(Some(12)),
+            endless: Endless(true),
+            rng: RngState::from(&rng),
+        };
+
+        let contents = ron::ser::to_string_pretty(&game, ron::ser::PrettyConfig::default()).unwrap();
+        let loaded = ron::from_str::<SavedGame>(&contents).unwrap();
+        assert_eq!(loaded, game);
+
+        // Restored generator continues the same stream
+        let mut restored = ChaCha8Rng::from(&loaded.rng);
+        for _ in 0..16 {
+            assert_eq!(restored.random::<u32>(), rng.random::<u32>());
+        }
+    }
+
+    #[test]
+    fn resumed_game_spawns_for_its_mode_test() {
+        let game = SavedGame {
+            version: SAVED_GAME_VERSION,
+            width: 4,
+            height: 4,
+            values: vec![Some(1), None, None, None, None, None, None, None, None, None, None, None, None, None, None, Some(2)],
+            score: 4,
+            moves: 1,
+            seed: 7,
+            mode: GameMode::Evil,
+            target: WinTarget::default(),
+            endless: Endless(false),
+            rng: RngState::from(&ChaCha8Rng::seed_from_u64(7)),
+        };
+
+        let mut app = App::new();
+        app.insert_resource(SavedGameResource(Some(game)))
+            .insert_resource(ContinueGame(true))
+            // Mode selected in the menu differs from the saved one
+            .insert_resource(GameMode::Kids)
+            .insert_resource(BoardStateResource(Board::new(4, 4)))
+            .insert_resource(GameParams { move_time: 0. })
+            .insert_resource(SharedRand(ChaCha8Rng::seed_from_u64(0)))
+            .init_resource::<BoardSize>()
+            .init_resource::<BoardLayout>()
+            .init_resource::<Score>()
+            .init_resource::<Moves>()
+            .init_resource::<PlayTime>()
+            .init_resource::<History>()
+            .init_resource::<GameSeed>()
+            .init_resource::<SeedSetting>()
+            .init_resource::<WinTarget>()
+            .init_resource::<Endless>()
+            .init_resource::<WatchReplay>()
+            .init_resource::<LoadedReplay>()
+            .init_resource::<ReplayRecording>()
+            .init_resource::<SpawnerResource>()
+            .add_systems(Update, game_setup_systems());
+        app.update();
+
+        assert_eq!(*app.world().resource::<GameMode>(), GameMode::Evil);
+        let board = Board::from_values(4, 4, &[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+        for seed in 0..20 {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let spawns = app.world_mut().resource_mut::<SpawnerResource>().spawn(&board, &mut rng);
+            assert_eq!(spawns, EvilSpawner::default().spawn(&board, &mut rng), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn won_game_is_not_kept_test() {
+        let game = SavedGame {
+            version: SAVED_GAME_VERSION,
+            width: 2,
+            height: 2,
+            values: vec![Some(11), None, None, Some(1)],
+            score: 20_000,
+            moves: 900,
+            seed: 1,
+            mode: GameMode::Classic,
+            target: WinTarget::default(),
+            endless: Endless(false),
+            rng: RngState::from(&ChaCha8Rng::seed_from_u64(1)),
+        };
+        let storage = MemoryStorage::default();
+        storage.write(SAVED_GAME_KEY, &ron::to_string(&game).unwrap()).unwrap();
+
+        let mut app = App::new();
+        app.add_plugins((StatesPlugin, save_plugin))
+            .insert_resource(StorageResource(Box::new(storage)))
+            .init_resource::<Endless>()
+            .init_resource::<WatchReplay>()
+            .init_resource::<LoadedReplay>()
+            .add_message::<NewGameMessage>()
+            .init_state::<AppState>()
+            .init_state::<GameState>();
+        app.update();
+        assert!(app.world().resource::<SavedGameResource>().0.is_some());
+
+        app.world_mut().resource_mut::<NextState<GameState>>().set(GameState::Win);
+        app.update();
+
+        assert!(app.world().resource::<SavedGameResource>().0.is_none());
+        assert_eq!(app.world().resource::<StorageResource>().read(SAVED_GAME_KEY), None);
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::game::ai::{search, AiConfig};
+use crate::game::components::{Board, Direction};
+use crate::game::mode::GameMode;
+use crate::game::rules::{Outcome, WinTarget};
+
+/// Player of simulated games
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bot {
+    /// Any legal move, picked at random
+    Random,
+    /// Legal move that scores the most right away
+    Greedy,
+    /// First legal move of down, left, right and up, keeps big tiles in a corner
+    Corner,
+    /// Expectimax search, without time budget so the same seed gives the same game
+    Expectimax(AiConfig),
+}
+
+impl Bot {
+    pub const NAMES: [&'static str; 4] = ["random", "greedy", "corner", "expectimax"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bot::Random => "random",
+            Bot::Greedy => "greedy",
+            Bot::Corner => "corner",
+            Bot::Expectimax(_) => "expectimax",
+        }
+    }
+
+    /// Name with search depth, to tell expectimax bots apart
+    pub fn label(&self) -> String {
+        match self {
+            Bot::Expectimax(config) => format!("expectimax-{}", config.depth),
+            _ => self.name().to_string(),
+        }
+    }
+
+    /// Bot by name, expectimax searches `depth` moves ahead
+    pub fn from_name(name: &str, depth: u32) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "random" => Some(Bot::Random),
+            "greedy" => Some(Bot::Greedy),
+            "corner" => Some(Bot::Corner),
+            "expectimax" => Some(Bot::Expectimax(AiConfig {
+                depth,
+                time_budget: None,
+                ..AiConfig::default()
+            })),
+            _ => None,
+        }
+    }
+
+    /// Move for the board, `None` when no move is left
+    pub fn choose(&self, values: &Board<usize>, rng: &mut impl Rng) -> Option<Direction> {
+        let legal_moves = values.legal_moves();
+
+        match self {
+            Bot::Random => (!legal_moves.is_empty()).then(|| legal_moves[rng.random_range(0..legal_moves.len())]),
+            // First of the best moves, in order of DIRECTIONS
+            Bot::Greedy => legal_moves.into_iter().rev().max_by_key(|&direction| {
+                let mut moved = values.clone();
+                moved.apply_move(direction).score
+            }),
+            Bot::Corner => [Direction::Down, Direction::Left, Direction::Right, Direction::Up]
+                .into_iter()
+                .find(|direction| legal_moves.contains(direction)),
+            Bot::Expectimax(config) => search(values, config).map(|hint| hint.direction),
+        }
+    }
+}
+
+/// Games to simulate, each game is played from its own seed
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SimConfig {
+    pub width: usize,
+    pub height: usize,
+    pub mode: GameMode,
+    pub target: WinTarget,
+    pub bot: Bot,
+    /// Play on after reaching the target, like "Keep going" in the game
+    pub keep_going: bool,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            width: 4,
+            height: 4,
+            mode: GameMode::default(),
+            target: WinTarget::default(),
+            bot: Bot::Corner,
+            keep_going: false,
+        }
+    }
+}
+
+/// Result of one simulated game
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct GameRecord {
+    pub seed: u64,
+    pub score: u64,
+    /// Biggest value on the board at the end, as power of two
+    pub max_value: usize,
+    pub moves: u64,
+    pub won: bool,
+}
+
+/// Plays a game to the end with the same spawns the game has for `seed`
+pub fn play_game(config: &SimConfig, seed: u64) -> GameRecord {
+    // Seeded the same way as a new game
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    // Bot draws numbers from its own stream, so spawns don't depend on the bot
+    let mut bot_rng = ChaCha8Rng::seed_from_u64(seed);
+    bot_rng.set_stream(1);
+
+    let rules = config.mode.rules(config.target);
+    let mut spawner = config.mode.spawner();
+    let mut values = Board::new(config.width, config.height);
+    for _ in 0..2 {
+        spawner.place(&mut values, &mut rng);
+    }
+
+    let mut record = GameRecord { seed, score: 0, max_value: 0, moves: 0, won: false };
+    while let Some(direction) = config.bot.choose(&values, &mut bot_rng) {
+        let outcome = values.apply_move(direction);
+        debug_assert!(outcome.changed, "Bot should only make legal moves");
+        record.score += outcome.score;
+        record.moves += 1;
+        spawner.place(&mut values, &mut rng);
+
+        match rules.evaluate(&values) {
+            Outcome::Win => {
+                record.won = true;
+                if !config.keep_going {
+                    break;
+                }
+            }
+            Outcome::Lose => break,
+            Outcome::Continue => {}
+        }
+    }
+
+    record.max_value = values.iter().flatten().copied().max().unwrap_or(0);
+    record
+}
+
+/// Spread of a number over games, percentiles are nearest-rank
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Distribution {
+    pub min: u64,
+    pub p10: u64,
+    pub median: u64,
+    pub p90: u64,
+    pub max: u64,
+    pub mean: f64,
+}
+
+impl Distribution {
+    pub fn new(mut samples: Vec<u64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+
+        let percentile = |percent: usize| samples[((samples.len() * percent).div_ceil(100)).max(1) - 1];
+        Self {
+            min: samples[0],
+            p10: percentile(10),
+            median: percentile(50),
+            p90: percentile(90),
+            max: samples[samples.len() - 1],
+            mean: samples.iter().sum::<u64>() as f64 / samples.len() as f64,
+        }
+    }
+
+    fn fields(&self) -> [(&'static str, String); 6] {
+        [
+            ("min", self.min.to_string()),
+            ("p10", self.p10.to_string()),
+            ("median", self.median.to_string()),
+            ("p90", self.p90.to_string()),
+            ("max", self.max.to_string()),
+            ("mean", format!("{:.2}", self.mean)),
+        ]
+    }
+}
+
+/// Summary of simulated games
+#[derive(Clone, PartialEq, Debug)]
+pub struct Report {
+    pub bot: String,
+    pub games: usize,
+    pub wins: usize,
+    pub score: Distribution,
+    pub moves: Distribution,
+    /// Number of games for every biggest tile, by face value
+    pub max_tiles: BTreeMap<u64, usize>,
+}
+
+impl Report {
+    pub fn new(config: &SimConfig, records: &[GameRecord]) -> Self {
+        let mut max_tiles = BTreeMap::new();
+        for record in records {
+            *max_tiles.entry(1u64 << record.max_value).or_insert(0) += 1;
+        }
+
+        Self {
+            bot: config.bot.label(),
+            games: records.len(),
+            wins: records.iter().filter(|record| record.won).count(),
+            score: Distribution::new(records.iter().map(|record| record.score).collect()),
+            moves: Distribution::new(records.iter().map(|record| record.moves).collect()),
+            max_tiles,
+        }
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        if self.games == 0 {
+            0.
+        } else {
+            self.wins as f64 / self.games as f64
+        }
+    }
+
+    /// Report as `section,key,value` rows
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("section,key,value\n");
+        let mut row = |section: &str, key: &str, value: &str| writeln!(csv, "{section},{key},{value}").unwrap();
+
+        row("summary", "bot", &self.bot);
+        row("summary", "games", &self.games.to_string());
+        row("summary", "wins", &self.wins.to_string());
+        row("summary", "win_rate", &format!("{:.4}", self.win_rate()));
+        for (key, value) in self.score.fields() {
+            row("score", key, &value);
+        }
+        for (key, value) in self.moves.fields() {
+            row("moves", key, &value);
+        }
+        for (tile, count) in &self.max_tiles {
+            row("max_tile", &tile.to_string(), &count.to_string());
+        }
+
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        let object = |fields: [(&str, String); 6]| {
+            let fields: Vec<String> = fields.iter().map(|(key, value)| format!("\"{key}\": {value}")).collect();
+            format!("{{{}}}", fields.join(", "))
+        };
+        let max_tiles: Vec<String> = self.max_tiles.iter().map(|(tile, count)| format!("\"{tile}\": {count}")).collect();
+
+        format!(
+            "{{\n  \"bot\": \"{}\",\n  \"games\": {},\n  \"wins\": {},\n  \"win_rate\": {:.4},\n  \"score\": {},\n  \"moves\": {},\n  \"max_tiles\": {{{}}}\n}}\n",
+            self.bot,
+            self.games,
+            self.wins,
+            self.win_rate(),
+            object(self.score.fields()),
+            object(self.moves.fields()),
+            max_tiles.join(", "),
+        )
+    }
+}
+
+/// Plays every bot over the same seeds on `threads` threads, best mean score first
+///
+/// Every game depends only on its bot and seed, so results don't depend on the number of threads.
+pub fn tournament(config: &SimConfig, bots: &[Bot], seeds: Range<u64>, threads: usize) -> Vec<Report> {
+    let games: Vec<(Bot, u64)> = bots.iter().flat_map(|&bot| seeds.clone().map(move |seed| (bot, seed))).collect();
+    let next_game = AtomicUsize::new(0);
+    let mut records = vec![None; games.len()];
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    // Games are taken one by one, slow bots don't hold up a whole share of games
+                    let mut played = Vec::new();
+                    loop {
+                        let game = next_game.fetch_add(1, Ordering::Relaxed);
+                        let Some(&(bot, seed)) = games.get(game) else {
+                            break played;
+                        };
+                        played.push((game, play_game(&SimConfig { bot, ..*config }, seed)));
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            for (game, record) in worker.join().expect("Simulation thread panicked") {
+                records[game] = Some(record);
+            }
+        }
+    });
+
+    let records: Vec<GameRecord> = records.into_iter().map(|record| record.expect("Every game is played")).collect();
+    let games_per_bot = (seeds.end.saturating_sub(seeds.start)) as usize;
+    let mut reports: Vec<Report> = bots
+        .iter()
+        .zip(records.chunks(games_per_bot.max(1)))
+        .map(|(&bot, records)| Report::new(&SimConfig { bot, ..*config }, records))
+        .collect();
+    // Stable sort keeps order of bots on ties
+    reports.sort_by(|a, b| b.score.mean.total_cmp(&a.score.mean).then(b.win_rate().total_cmp(&a.win_rate())));
+
+    reports
+}
+
+const RANKING_COLUMNS: [&str; 10] =
+    ["rank", "bot", "games", "wins", "win_rate", "score_mean", "score_median", "score_p90", "max_tile", "moves_mean"];
+
+fn ranking_rows(reports: &[Report]) -> Vec<[String; 10]> {
+    reports
+        .iter()
+        .enumerate()
+        .map(|(rank, report)| {
+            [
+                (rank + 1).to_string(),
+                report.bot.clone(),
+                report.games.to_string(),
+                report.wins.to_string(),
+                format!("{:.4}", report.win_rate()),
+                format!("{:.2}", report.score.mean),
+                report.score.median.to_string(),
+                report.score.p90.to_string(),
+                report.max_tiles.keys().last().copied().unwrap_or(0).to_string(),
+                format!("{:.2}", report.moves.mean),
+            ]
+        })
+        .collect()
+}
+
+/// Reports in order of rank as a table aligned for reading
+pub fn ranking_table(reports: &[Report]) -> String {
+    let rows = ranking_rows(reports);
+    let widths: Vec<usize> = (0..RANKING_COLUMNS.len())
+        .map(|column| rows.iter().map(|row| row[column].len()).chain([RANKING_COLUMNS[column].len()]).max().unwrap_or(0))
+        .collect();
+
+    let mut table = String::new();
+    for row in [RANKING_COLUMNS.map(String::from)].iter().chain(&rows) {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, &width)| format!("{cell:<width$}")).collect();
+        writeln!(table, "{}", cells.join("  ").trim_end()).unwrap();
+    }
+
+    table
+}
+
+/// Reports in order of rank, one row per bot
+pub fn ranking_csv(reports: &[Report]) -> String {
+    let mut csv = format!("{}\n", RANKING_COLUMNS.join(","));
+    for row in ranking_rows(reports) {
+        writeln!(csv, "{}", row.join(",")).unwrap();
+    }
+
+    csv
+}
+
+/// Reports in order of rank as JSON array
+pub fn ranking_json(reports: &[Report]) -> String {
+    let reports: Vec<String> = reports.iter().map(|report| report.to_json().trim_end().to_string()).collect();
+    format!("[\n{}\n]\n", reports.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_game_test() {
+        let config = SimConfig { bot: Bot::Random, ..SimConfig::default() };
+
+        assert_eq!(play_game(&config, 42), play_game(&config, 42));
+        assert_ne!(play_game(&config, 42), play_game(&config, 43));
+    }
+
+    #[test]
+    fn game_plays_to_the_end_test() {
+        for bot in [Bot::Random, Bot::Greedy, Bot::Corner, Bot::from_name("expectimax", 1).unwrap()] {
+            let config = SimConfig { width: 3, height: 3, bot, ..SimConfig::default() };
+            let record = play_game(&config, 7);
+
+            assert!(record.moves > 0);
+            assert!(record.max_value >= 2);
+            // 3x3 board can't reach 2048
+            assert!(!record.won);
+        }
+    }
+
+    #[test]
+    fn win_ends_the_game_test() {
+        let config = SimConfig { mode: GameMode::Kids, bot: Bot::from_name("expectimax", 2).unwrap(), ..SimConfig::default() };
+        let record = play_game(&config, 1);
+
+        assert!(record.won);
+        assert_eq!(record.max_value, 9);
+    }
+
+    #[test]
+    fn bot_choice_test() {
+        let values = Board::from_cells(4, 1, vec![Some(1), Some(2), Some(2), None]);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        assert_eq!(Bot::Greedy.choose(&values, &mut rng), Some(Direction::Left));
+        assert_eq!(Bot::Corner.choose(&values, &mut rng), Some(Direction::Left));
+        assert_eq!(Bot::Random.choose(&Board::from_cells(2, 1, vec![Some(1), Some(2)]), &mut rng), None);
+    }
+
+    #[test]
+    fn report_test() {
+        let records: Vec<GameRecord> = (1..=10)
+            .map(|seed| GameRecord { seed, score: seed * 100, max_value: if seed > 8 { 11 } else { 10 }, moves: seed, won: seed > 8 })
+            .collect();
+        let report = Report::new(&SimConfig::default(), &records);
+
+        assert_eq!(report.win_rate(), 0.2);
+        assert_eq!(report.score, Distribution { min: 100, p10: 100, median: 500, p90: 900, max: 1000, mean: 550. });
+        assert_eq!(report.max_tiles, BTreeMap::from([(1024, 8), (2048, 2)]));
+        assert!(report.to_csv().contains("summary,win_rate,0.2000\n"));
+        assert!(report.to_csv().contains("max_tile,2048,2\n"));
+        assert!(report.to_json().contains("\"max_tiles\": {\"1024\": 8, \"2048\": 2}"));
+    }
+
+    #[test]
+    fn tournament_test() {
+        let config = SimConfig { width: 3, height: 3, ..SimConfig::default() };
+        let bots = [Bot::Random, Bot::Greedy, Bot::Corner, Bot::from_name("expectimax", 1).unwrap()];
+
+        let reports = tournament(&config, &bots, 10..16, 1);
+        assert_eq!(reports, tournament(&config, &bots, 10..16, 4));
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|report| report.games == 6));
+        assert!(reports.windows(2).all(|pair| pair[0].score.mean >= pair[1].score.mean));
+
+        // Same games as played one by one
+        let random = reports.iter().find(|report| report.bot == "random").unwrap();
+        let records: Vec<GameRecord> = (10..16).map(|seed| play_game(&SimConfig { bot: Bot::Random, ..config }, seed)).collect();
+        assert_eq!(*random, Report::new(&SimConfig { bot: Bot::Random, ..config }, &records));
+
+        let table = ranking_table(&reports);
+        assert_eq!(table.lines().count(), 5);
+        assert!(table.starts_with("rank  bot"));
+        assert_eq!(ranking_csv(&reports).lines().next(), Some(RANKING_COLUMNS.join(",").as_str()));
+    }
+}
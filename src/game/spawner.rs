@@ -0,0 +1,295 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use crate::game::ai::{worst_spawn, Heuristics};
+use crate::game::bitboard::Bitboard;
+use crate::game::components::{Board, TileSpawn};
+
+/// Where new tiles go after a move and what values they have
+pub trait Spawner: Send + Sync {
+    /// Tiles to spawn on `board`, values are powers of two, empty when the board is full
+    fn spawn(&mut self, board: &Board<usize>, rng: &mut ChaCha8Rng) -> Vec<TileSpawn>;
+
+    /// Spawns tiles and places them on `board`
+    fn place(&mut self, board: &mut Board<usize>, rng: &mut ChaCha8Rng) -> Vec<TileSpawn> {
+        let spawns = self.spawn(board, rng);
+        for spawn in &spawns {
+            let index = spawn.col + spawn.row * board.width;
+            board[index] = Some(spawn.value);
+        }
+
+        spawns
+    }
+}
+
+/// Spawner of the current game, set up when game starts
+#[derive(Resource, Deref, DerefMut)]
+pub struct SpawnerResource(pub Box<dyn Spawner>);
+
+impl Default for SpawnerResource {
+    fn default() -> Self {
+        Self(Box::new(WeightedSpawner::classic()))
+    }
+}
+
+/// Draws one of the values, in proportion to their weights
+///
+/// Values are drawn from the last one down, one draw for every value until one is taken,
+/// so classic weights draw exactly like [crate::game::engine::random_spawn].
+fn draw_value(weights: &[(usize, u32)], rng: &mut ChaCha8Rng) -> usize {
+    let mut total: u32 = weights.iter().map(|(_, weight)| weight).sum();
+    for &(value, weight) in weights[1..].iter().rev() {
+        if rng.random_ratio(weight, total) {
+            return value;
+        }
+        total -= weight;
+    }
+
+    weights[0].0
+}
+
+/// Single tile on a random empty tile, value is drawn before the tile
+fn random_tile(board: &Board<usize>, weights: &[(usize, u32)], rng: &mut ChaCha8Rng) -> Vec<TileSpawn> {
+    let empty_spaces = board.empty_indices();
+    if empty_spaces.is_empty() {
+        return Vec::new();
+    }
+
+    let value = draw_value(weights, rng);
+    let index = empty_spaces[rng.random_range(0..empty_spaces.len())];
+    vec![TileSpawn { col: index % board.width, row: index / board.width, value }]
+}
+
+/// One tile on a random empty tile, with values of given weights
+#[derive(Clone, PartialEq, Debug)]
+pub struct WeightedSpawner {
+    weights: Vec<(usize, u32)>,
+}
+
+impl WeightedSpawner {
+    /// `weights` pairs values, as powers of two, with their relative chance of spawning
+    pub fn new(weights: Vec<(usize, u32)>) -> Self {
+        let weights: Vec<_> = weights.into_iter().filter(|&(_, weight)| weight > 0).collect();
+        assert!(!weights.is_empty(), "Spawner needs a value with weight above 0");
+
+        Self { weights }
+    }
+
+    /// 4 in one of five spawns and 2 otherwise, same draws as before spawners
+    pub fn classic() -> Self {
+        Self::new(vec![(1, 4), (2, 1)])
+    }
+}
+
+impl Spawner for WeightedSpawner {
+    fn spawn(&mut self, board: &Board<usize>, rng: &mut ChaCha8Rng) -> Vec<TileSpawn> {
+        random_tile(board, &self.weights, rng)
+    }
+}
+
+/// Several tiles per move from another spawner, as many as fit on the board
+pub struct MultiSpawner {
+    inner: Box<dyn Spawner>,
+    count: usize,
+}
+
+impl MultiSpawner {
+    pub fn new(inner: Box<dyn Spawner>, count: usize) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl Spawner for MultiSpawner {
+    fn spawn(&mut self, board: &Board<usize>, rng: &mut ChaCha8Rng) -> Vec<TileSpawn> {
+        // Tiles are placed on a copy, so the next one doesn't land on them
+        let mut board = board.clone();
+        let mut spawns = Vec::new();
+        for _ in 0..self.count {
+            let placed = self.inner.place(&mut board, rng);
+            if placed.is_empty() {
+                break;
+            }
+            spawns.extend(placed);
+        }
+
+        spawns
+    }
+}
+
+/// Values grow with the biggest tile on the board, `spread` steps below it, 2 at least
+///
+/// One in five spawns is the next value up, like 4 is for classic spawns.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ScalingSpawner {
+    pub spread: usize,
+}
+
+impl Spawner for ScalingSpawner {
+    fn spawn(&mut self, board: &Board<usize>, rng: &mut ChaCha8Rng) -> Vec<TileSpawn> {
+        let max_value = board.iter().flatten().copied().max().unwrap_or(0);
+        let base = max_value.saturating_sub(self.spread).max(1);
+
+        random_tile(board, &[(base, 4), (base + 1, 1)], rng)
+    }
+}
+
+/// Spawns from a list in order, for puzzles and tutorials
+///
+/// Spawn of the list on an occupied tile is skipped, `fallback` spawns instead of it and after the list.
+pub struct ScriptedSpawner {
+    spawns: Vec<TileSpawn>,
+    position: usize,
+    fallback: Box<dyn Spawner>,
+}
+
+impl ScriptedSpawner {
+    pub fn new(spawns: Vec<TileSpawn>, fallback: Box<dyn Spawner>) -> Self {
+        Self { spawns, position: 0, fallback }
+    }
+}
+
+impl Spawner for ScriptedSpawner {
+    fn spawn(&mut self, board: &Board<usize>, rng: &mut ChaCha8Rng) -> Vec<TileSpawn> {
+        let Some(&spawn) = self.spawns.get(self.position) else {
+            return self.fallback.spawn(board, rng);
+        };
+        self.position += 1;
+
+        let fits = spawn.col < board.width && spawn.row < board.height;
+        if fits && board[spawn.col + spawn.row * board.width].is_none() {
+            vec![spawn]
+        } else {
+            self.fallback.spawn(board, rng)
+        }
+    }
+}
+
+/// Tile the player can do the least about, on the worst empty tile with the worst value
+///
+/// Looks `depth` player moves ahead, see [worst_spawn]. No random numbers are drawn.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EvilSpawner {
+    pub depth: u32,
+    pub heuristics: Heuristics,
+}
+
+impl Default for EvilSpawner {
+    fn default() -> Self {
+        Self { depth: 2, heuristics: Heuristics::default() }
+    }
+}
+
+impl Spawner for EvilSpawner {
+    fn spawn(&mut self, board: &Board<usize>, _rng: &mut ChaCha8Rng) -> Vec<TileSpawn> {
+        let spawn = match Bitboard::from_board(board) {
+            Some(bitboard) => worst_spawn(&bitboard, self.depth, &self.heuristics),
+            None => worst_spawn(board, self.depth, &self.heuristics),
+        };
+
+        spawn
+            .map(|(index, value)| TileSpawn { col: index % board.width, row: index / board.width, value })
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::ai::{search, AiConfig};
+    use crate::game::engine::random_spawn;
+    use rand::SeedableRng;
+
+    #[test]
+    fn classic_spawner_draws_like_random_spawn_test() {
+        for seed in 0..200 {
+            let values = Board::from_values(3, 3, &[0, 1, 0, 2, 0, 0, 3, 0, 1]);
+            let mut classic_rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+            let spawns = WeightedSpawner::classic().spawn(&values, &mut classic_rng);
+            assert_eq!(spawns, random_spawn(&values, &mut rng).into_iter().collect::<Vec<_>>());
+            // Both drew the same numbers
+            assert_eq!(classic_rng.random::<u64>(), rng.random::<u64>());
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        assert_eq!(WeightedSpawner::classic().spawn(&Board::from_values(1, 2, &[1, 1]), &mut rng), vec![]);
+    }
+
+    #[test]
+    fn weighted_spawner_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let mut spawner = WeightedSpawner::new(vec![(1, 0), (3, 1), (4, 1)]);
+        let values = Board::new(4, 4);
+
+        let spawned: Vec<_> = (0..100).flat_map(|_| spawner.spawn(&values, &mut rng)).map(|spawn| spawn.value).collect();
+        assert!(spawned.contains(&3) && spawned.contains(&4));
+        assert!(spawned.iter().all(|&value| value == 3 || value == 4));
+    }
+
+    #[test]
+    fn multi_spawner_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut spawner = MultiSpawner::new(Box::new(WeightedSpawner::classic()), 3);
+
+        let mut values = Board::from_values(2, 2, &[1, 0, 0, 0]);
+        let spawns = spawner.place(&mut values, &mut rng);
+        assert_eq!(spawns.len(), 3);
+        assert!(values.empty_indices().is_empty());
+        assert_eq!(spawner.spawn(&values, &mut rng), vec![]);
+
+        let spawns = spawner.spawn(&Board::from_values(2, 2, &[1, 0, 1, 1]), &mut rng);
+        assert_eq!(spawns.iter().map(|spawn| (spawn.col, spawn.row)).collect::<Vec<_>>(), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn scaling_spawner_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(9);
+        let mut spawner = ScalingSpawner { spread: 3 };
+
+        for _ in 0..50 {
+            let value = spawner.spawn(&Board::from_values(2, 2, &[10, 0, 2, 0]), &mut rng)[0].value;
+            assert!(value == 7 || value == 8, "{value}");
+
+            let value = spawner.spawn(&Board::new(2, 2), &mut rng)[0].value;
+            assert!(value == 1 || value == 2, "{value}");
+        }
+    }
+
+    #[test]
+    fn evil_spawner_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(13);
+        let mut spawner = EvilSpawner::default();
+
+        assert_eq!(spawner.spawn(&Board::from_values(2, 2, &[1, 2, 3, 0]), &mut rng), vec![TileSpawn { col: 1, row: 1, value: 1 }]);
+        assert_eq!(spawner.spawn(&Board::from_values(2, 2, &[1, 2, 3, 4]), &mut rng), vec![]);
+
+        // Searching bot doesn't get to 256 against it
+        let mut values = Board::new(4, 4);
+        spawner.place(&mut values, &mut rng);
+        spawner.place(&mut values, &mut rng);
+        let config = AiConfig { depth: 1, time_budget: None, ..AiConfig::default() };
+        while let Some(hint) = search(&values, &config) {
+            values.apply_move(hint.direction);
+            spawner.place(&mut values, &mut rng);
+        }
+        assert!(values.iter().flatten().all(|&value| value < 8), "{values:?}");
+    }
+
+    #[test]
+    fn scripted_spawner_test() {
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+        let script = vec![TileSpawn { col: 1, row: 1, value: 5 }, TileSpawn { col: 0, row: 0, value: 6 }];
+        let mut spawner = ScriptedSpawner::new(script, Box::new(WeightedSpawner::new(vec![(2, 1)])));
+
+        let mut values = Board::from_values(2, 2, &[1, 0, 0, 0]);
+        assert_eq!(spawner.place(&mut values, &mut rng), vec![TileSpawn { col: 1, row: 1, value: 5 }]);
+        // Second spawn of the script is on an occupied tile
+        let spawns = spawner.place(&mut values, &mut rng);
+        assert_eq!(spawns.len(), 1);
+        assert_eq!(spawns[0].value, 2);
+        assert_eq!(spawner.place(&mut values, &mut rng)[0].value, 2);
+        assert_eq!(spawner.place(&mut values, &mut rng), vec![]);
+    }
+}
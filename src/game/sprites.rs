@@ -1,11 +1,14 @@
 use bevy::app::Startup;
 use bevy::asset::AssetServer;
 use bevy::math::UVec2;
-use bevy::prelude::{App, Assets, Commands, Deref, DerefMut, Handle, Image, Res, ResMut, Resource, Sprite, TextureAtlas, TextureAtlasLayout, Timer, TimerMode};
+use bevy::prelude::{App, Assets, Color, Commands, Deref, DerefMut, Handle, Image, Res, ResMut, Resource, Sprite, TextureAtlas, TextureAtlasLayout, Timer, TimerMode};
 use std::collections::HashMap;
 
 use crate::animation_sprite::{AnimationBundle, AnimationIndices, AnimationTimer};
 
+/// Highest value with its own sprite, higher values reuse it with a tint
+pub const MAX_SPRITE_VALUE: usize = 11;
+
 pub fn sprites_plugin(app: &mut App) {
     app.add_systems(Startup, init_board_sprites);
 }
@@ -25,6 +28,18 @@ pub struct BoardSprites {
 
 impl BoardSprites {
 
+    /// Sprite for the value, values above [MAX_SPRITE_VALUE] get the last sprite tinted by how far above they are
+    pub fn for_value(&self, value: usize) -> Option<AnimationBundle> {
+        if value <= MAX_SPRITE_VALUE {
+            return self.get(&value).cloned();
+        }
+
+        let hue = ((value - MAX_SPRITE_VALUE) as f32 * 67.) % 360.;
+        self.get(&MAX_SPRITE_VALUE)
+            .cloned()
+            .map(|bundle| bundle.with_color(Color::hsl(hue, 0.8, 0.7)))
+    }
+
     fn new(
         asset_server: Res<AssetServer>,
         mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>
@@ -81,4 +96,40 @@ impl BoardSprites {
         )
     }
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::{Entity, World};
+
+    #[test]
+    fn for_value_test() {
+        let layout = Handle::<TextureAtlasLayout>::default();
+        let board_sprites = BoardSprites {
+            bundles: (1..=MAX_SPRITE_VALUE)
+                .map(|value| {
+                    let indices = (value * 2 - 2, value * 2 - 1);
+                    (value, BoardSprites::construct_animation_bundle(Handle::default(), indices, layout.clone()))
+                })
+                .collect(),
+        };
+        assert!(board_sprites.for_value(0).is_none());
+
+        let mut world = World::new();
+        let regular = world.spawn(board_sprites.for_value(5).unwrap()).id();
+        let last = world.spawn(board_sprites.for_value(11).unwrap()).id();
+        let above = world.spawn(board_sprites.for_value(12).unwrap()).id();
+        let further_above = world.spawn(board_sprites.for_value(13).unwrap()).id();
+        let sprite = |entity: Entity| world.get::<Sprite>(entity).unwrap();
+        let atlas_index = |entity: Entity| sprite(entity).texture_atlas.as_ref().unwrap().index;
+
+        assert_eq!(atlas_index(regular), 8);
+        assert_eq!(atlas_index(last), 20);
+        assert_eq!(atlas_index(above), 20);
+        assert_eq!(atlas_index(further_above), 20);
+
+        assert_eq!(sprite(last).color, Color::WHITE);
+        assert_ne!(sprite(above).color, Color::WHITE);
+        assert_ne!(sprite(above).color, sprite(further_above).color);
+    }
+}
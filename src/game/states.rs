@@ -11,6 +11,14 @@ pub enum GameState {
     Decision,
 }
 
+/// Game is frozen with [GameSet] and virtual time while paused
+#[derive(States, Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
 #[derive(SystemSet, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct GameSet;
 
@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::game::autoplay::autoplay_game;
+use crate::game::components::*;
+use crate::game::mode::Endless;
+use crate::game::playback::playing_replay;
+use crate::game::save::continuing_game;
+use crate::game::states::*;
+use crate::menu::AppState;
+use crate::storage::StorageResource;
+
+const STATISTICS_KEY: &str = "statistics.ron";
+/// Version of [StatisticsFile], files with other version are ignored
+const STATISTICS_VERSION: u32 = 1;
+
+/// Best score and lifetime statistics, kept between launches
+#[derive(Resource, Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Statistics {
+    pub best_score: u64,
+    pub games_played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    /// Highest [Value] ever reached, power of two
+    pub highest_value: usize,
+    pub total_merges: u64,
+    pub total_moves: u64,
+}
+
+/// Layout of statistics file in storage
+#[derive(Serialize, Deserialize)]
+struct StatisticsFile {
+    version: u32,
+    statistics: Statistics,
+}
+
+pub fn stats_plugin(app: &mut App) {
+    app.init_resource::<Statistics>()
+        .add_systems(Startup, load_statistics)
+        // Watched replays and games of the bot don't count
+        .add_systems(
+            OnEnter(AppState::Game),
+            count_game_played.run_if(not(continuing_game).and(not(playing_replay)).and(not(autoplay_game))),
+        )
+        .add_systems(
+            Update,
+            (
+                count_merges_system,
+                count_game_played.run_if(on_message::<NewGameMessage>),
+                update_best_score.run_if(resource_changed::<Score>),
+            )
+                .run_if(not(playing_replay).and(not(autoplay_game)))
+                .in_set(GameSet),
+        )
+        .add_systems(OnEnter(GameState::Decision), count_move.run_if(not(playing_replay).and(not(autoplay_game))))
+        .add_systems(
+            OnEnter(GameState::Win),
+            (count_win, save_statistics).chain().run_if(not(playing_replay).and(not(autoplay_game))),
+        )
+        // Game kept going after the win counts as won only
+        .add_systems(
+            OnEnter(GameState::Lose),
+            (count_loss, save_statistics)
+                .chain()
+                .run_if(not(playing_replay).and(not(autoplay_game)).and(not(won_game))),
+        )
+        // Undo out of the loss takes it back, losing again counts once
+        .add_systems(
+            Update,
+            (uncount_loss, save_statistics)
+                .chain()
+                .run_if(on_message::<LossUndoneMessage>)
+                .run_if(not(playing_replay).and(not(autoplay_game)).and(not(won_game)))
+                .in_set(GameSet),
+        )
+        .add_systems(OnExit(AppState::Game), save_statistics)
+        .add_systems(Last, save_statistics.run_if(on_message::<AppExit>));
+}
+
+/// Run condition for games won before, "Keep going" makes them endless
+fn won_game(endless: Res<Endless>) -> bool {
+    endless.0
+}
+
+fn load_statistics(
+    storage: Res<StorageResource>,
+    mut statistics: ResMut<Statistics>,
+) {
+    let Some(contents) = storage.read(STATISTICS_KEY) else {
+        debug!("No statistics stored yet");
+        return;
+    };
+
+    match ron::from_str::<StatisticsFile>(&contents) {
+        Ok(file) if file.version == STATISTICS_VERSION => *statistics = file.statistics,
+        Ok(file) => warn!("Statistics version {} is not supported, starting from scratch", file.version),
+        Err(e) => warn!("Statistics can't be read, starting from scratch: {}", e),
+    }
+}
+
+fn save_statistics(
+    storage: Res<StorageResource>,
+    statistics: Res<Statistics>,
+) {
+    let file = StatisticsFile {
+        version: STATISTICS_VERSION,
+        statistics: statistics.clone(),
+    };
+
+    let contents = match ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warn!("Statistics can't be serialized: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = storage.write(STATISTICS_KEY, &contents) {
+        warn!("Statistics can't be saved: {}", e);
+    }
+}
+
+fn count_game_played(mut statistics: ResMut<Statistics>) {
+    statistics.games_played += 1;
+}
+
+fn count_move(mut statistics: ResMut<Statistics>) {
+    statistics.total_moves += 1;
+}
+
+fn count_win(mut statistics: ResMut<Statistics>) {
+    statistics.wins += 1;
+}
+
+fn count_loss(mut statistics: ResMut<Statistics>) {
+    statistics.losses += 1;
+}
+
+fn uncount_loss(mut statistics: ResMut<Statistics>) {
+    statistics.losses = statistics.losses.saturating_sub(1);
+}
+
+fn count_merges_system(
+    mut statistics: ResMut<Statistics>,
+    mut merge_messages: MessageReader<MergeMessage>,
+) {
+    for MergeMessage(value) in merge_messages.read() {
+        statistics.total_merges += 1;
+        statistics.highest_value = statistics.highest_value.max(*value);
+    }
+}
+
+fn update_best_score(
+    score: Res<Score>,
+    mut statistics: ResMut<Statistics>,
+) {
+    if score.0 > statistics.best_score {
+        statistics.best_score = score.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::state::app::StatesPlugin;
+    use crate::game::mode::GameMode;
+    use crate::game::playback::{LoadedReplay, WatchReplay};
+    use crate::game::save::{ContinueGame, SavedGameResource};
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn statistics_file_test() {
+        let statistics = Statistics {
+            best_score: 20_480,
+            games_played: 12,
+            wins: 3,
+            losses: 8,
+            highest_value: 11,
+            total_merges: 4096,
+            total_moves: 5000,
+        };
+        let file = StatisticsFile { version: STATISTICS_VERSION, statistics: statistics.clone() };
+        let contents = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).unwrap();
+
+        assert_eq!(ron::from_str::<StatisticsFile>(&contents).unwrap().statistics, statistics);
+        // Fields missing in older files start from zero
+        let loaded: StatisticsFile = ron::from_str("(version: 1, statistics: (wins: 2))").unwrap();
+        assert_eq!(loaded.statistics, Statistics { wins: 2, ..default() });
+    }
+
+    #[test]
+    fn count_wins_and_losses_test() {
+        let mut app = App::new();
+        app.add_plugins((StatesPlugin, stats_plugin))
+            .insert_resource(StorageResource(Box::new(MemoryStorage::default())))
+            .init_resource::<Score>()
+            .init_resource::<Endless>()
+            .init_resource::<GameMode>()
+            .init_resource::<WatchReplay>()
+            .init_resource::<LoadedReplay>()
+            .init_resource::<ContinueGame>()
+            .init_resource::<SavedGameResource>()
+            .add_message::<MergeMessage>()
+            .add_message::<NewGameMessage>()
+            .add_message::<LossUndoneMessage>()
+            .add_message::<AppExit>()
+            .init_state::<AppState>()
+            .init_state::<GameState>();
+        let enter = |app: &mut App, state| {
+            app.world_mut().resource_mut::<NextState<GameState>>().set(state);
+            app.update();
+        };
+        let counts = |app: &App| {
+            let statistics = app.world().resource::<Statistics>();
+            (statistics.wins, statistics.losses)
+        };
+
+        enter(&mut app, GameState::Win);
+        assert_eq!(counts(&app), (1, 0));
+
+        // Loss after "Keep going"
+        app.insert_resource(Endless(true));
+        enter(&mut app, GameState::Wait);
+        enter(&mut app, GameState::Lose);
+        assert_eq!(counts(&app), (1, 0));
+
+        app.insert_resource(Endless(false));
+        enter(&mut app, GameState::Wait);
+        enter(&mut app, GameState::Lose);
+        assert_eq!(counts(&app), (1, 1));
+
+        app.world_mut().write_message(LossUndoneMessage);
+        enter(&mut app, GameState::Wait);
+        assert_eq!(counts(&app), (1, 0));
+
+        // Saved along the way
+        let storage = app.world().resource::<StorageResource>();
+        let file: StatisticsFile = ron::from_str(&storage.read(STATISTICS_KEY).unwrap()).unwrap();
+        assert_eq!(file.statistics, *app.world().resource::<Statistics>());
+    }
+}
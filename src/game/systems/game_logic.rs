@@ -1,10 +1,15 @@
 use crate::game::components::*;
+use crate::game::effects::EffectMarker;
+use crate::game::mode::{Endless, GameMode};
+use crate::game::rules::{Outcome, RulesResource, WinTarget};
+use crate::game::spawner::SpawnerResource;
 use crate::game::states::*;
 use crate::game::utils::*;
 use crate::game::*;
 use crate::SharedRand;
 use bevy::prelude::*;
-use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
 const COLLISION_EPSILON: f32 = 0.96;
 
@@ -12,14 +17,16 @@ pub fn produce_block_bundle(
     col: usize,
     row: usize,
     val: usize,
+    layout: &BoardLayout,
 ) -> (Block, Transform, Collider, Position, Value, OnGameScreen) {
     (
         Block,
         Transform::from_translation(Vec3::new(
-            col_to_x(col as i32, RECT_SIZE),
-            row_to_y(row as i32, RECT_SIZE),
+            col_to_x(col as i32, layout),
+            row_to_y(row as i32, layout),
             9.,
-        )),
+        ))
+        .with_scale(Vec3::splat(layout.scale())),
         Collider,
         Position(col, row),
         Value(val),
@@ -27,146 +34,144 @@ pub fn produce_block_bundle(
     )
 }
 
-pub fn is_neighbours_mergeable(
-    board: &Board<4, Entity>,
+/// Board with values of blocks instead of entities
+pub fn values_board(
+    board: &Board<Entity>,
     block_query: &Query<&Value, With<Block>>,
-    col: usize,
-    row: usize,
-) -> bool {
-    let tile = extract_value(board, block_query, col + row * 4);
-    let range = 0..4i32;
-
-    if tile == 0 {
-        return true;
-    } else {
-        if range.contains(&(col as i32 + 1))
-            && (tile == extract_value(board, block_query, col + 1 + row * 4))
-        {
-            return true;
-        }
-        if range.contains(&(col as i32 - 1))
-            && (tile == extract_value(board, block_query, col - 1 + row * 4))
-        {
-            return true;
-        }
-        if range.contains(&(row as i32 + 1))
-            && (tile == extract_value(board, block_query, col + (row + 1) * 4))
-        {
-            return true;
-        }
-        if range.contains(&(row as i32 - 1))
-            && (tile == extract_value(board, block_query, col + (row - 1) * 4))
-        {
-            return true;
-        }
-    }
-
-    false
+) -> Board<usize> {
+    Board::from_cells(
+        board.width,
+        board.height,
+        board
+            .iter()
+            .map(|tile| tile.and_then(|entity| block_query.get(entity).ok()).map(|value| value.0))
+            .collect(),
+    )
 }
 
-pub fn extract_value(
-    board: &Board<4, Entity>,
-    block_query: &Query<&Value, With<Block>>,
-    index: usize,
-) -> usize {
-    let tile = board[index];
-    if tile.is_none() {
-        0
-    } else {
-        let tile_value = block_query
-            .get(tile.unwrap())
-            .expect("All block should have value and be on board");
-        tile_value.0
+pub fn the_end_system(
+    mut game_state: ResMut<NextState<GameState>>,
+    board_state_resource: Res<BoardStateResource>,
+    rules: Res<RulesResource>,
+    endless: Res<Endless>,
+    block_query: Query<&Value, With<Block>>,
+) {
+    let values = values_board(&board_state_resource.0, &block_query);
+
+    match rules.evaluate(&values) {
+        Outcome::Win if !endless.0 => game_state.set(GameState::Win),
+        // Target stays on the board of endless games, a stuck one would wait for a move forever
+        Outcome::Win if values.is_terminal() => game_state.set(GameState::Lose),
+        Outcome::Lose => game_state.set(GameState::Lose),
+        Outcome::Win | Outcome::Continue => game_state.set(GameState::Wait),
     }
 }
 
-pub fn rotate_board<const N: usize, T: Copy>(
-    board: &Board<N, T>,
-    rotate_by: RotateBy,
-) -> Board<N, T> {
-    let mut rotated: Vec<Option<T>> = vec![None; N * N];
-
-    for i in 0..N {
-        for j in 0..N {
-            let (c, r) = rotate_index::<N>(i, j, rotate_by);
-            rotated[c + r * N] = board[i + j * N];
-        }
-    }
+/// Rules for the game from chosen mode and target
+pub fn rules_setup(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    win_target: Res<WinTarget>,
+) {
+    commands.insert_resource(RulesResource(game_mode.rules(*win_target)));
+}
 
-    Board(rotated)
+/// Spawner for the game from chosen mode
+pub fn spawner_setup(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+) {
+    commands.insert_resource(SpawnerResource(game_mode.spawner()));
 }
 
-pub fn rotate_index<const N: usize>(c: usize, r: usize, rotate_by: RotateBy) -> (usize, usize) {
-    match rotate_by {
-        RotateBy::None => (c, r),
-        RotateBy::Left => (r, N - 1 - c),
-        RotateBy::Right => (N - 1 - r, c),
-        RotateBy::Full => (N - 1 - c, N - 1 - r),
-    }
+/// Seeds random numbers of the new game, with seed from settings or a random one
+pub fn seed_setup(
+    seed_setting: Res<SeedSetting>,
+    mut game_seed: ResMut<GameSeed>,
+    mut shared_rand: ResMut<SharedRand>,
+) {
+    *game_seed = seed_setting.0.map(GameSeed).unwrap_or_else(GameSeed::random);
+    shared_rand.0 = ChaCha8Rng::seed_from_u64(game_seed.0);
+    debug!("Game seed {}", game_seed.0);
 }
 
-pub fn the_end_system(
+/// Removes blocks with their moves and merge effects, and empties the board for the next game
+#[allow(clippy::type_complexity)]
+pub fn clear_board_system(
+    mut commands: Commands,
     mut game_state: ResMut<NextState<GameState>>,
-    board_state_resource: Res<BoardStateResource>,
-    block_query: Query<&Value, With<Block>>,
+    mut board_state_resource: ResMut<BoardStateResource>,
+    block_query: Query<Entity, Or<(With<Block>, With<QueuedMove>, With<EffectMarker>)>>,
 ) {
-    let board = &board_state_resource.0;
-    for c in 0..4 {
-        for r in 0..4 {
-            let tile_value = extract_value(board, &block_query, c + r * 4);
-            if tile_value == 11 {
-                // win
-                game_state.set(GameState::Win);
-                return;
-            }
-
-            if is_neighbours_mergeable(board, &block_query, c, r) {
-                game_state.set(GameState::Wait);
-                return;
-            }
-        }
+    for entity in block_query.iter() {
+        // Effects are children of blocks and may be gone with them already
+        commands.entity(entity).try_despawn();
     }
+    let board = &mut board_state_resource.0;
+    *board = Board::new(board.width, board.height);
+    game_state.set(GameState::Wait);
+}
 
-    // game over
-    game_state.set(GameState::Lose);
+/// Leaves the next game nothing of this one, entities go with [OnGameScreen]
+pub fn reset_game_system(
+    mut game_state: ResMut<NextState<GameState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut board_state_resource: ResMut<BoardStateResource>,
+    mut forced_spawn: ResMut<ForcedSpawn>,
+) {
+    game_state.set(GameState::Wait);
+    pause_state.set(PauseState::Running);
+    let board = &mut board_state_resource.0;
+    *board = Board::new(board.width, board.height);
+    forced_spawn.0 = None;
 }
 
 #[inline]
-pub fn acquire_empty_tile<const N: usize>(
+pub fn acquire_empty_tiles(
+    spawner: &mut SpawnerResource,
     shared_rand: &mut SharedRand,
-    board: &Board<N, Entity>,
-) -> Option<(usize, usize, usize)> {
-    let empty_spaces = board.empty_indices();
-    if empty_spaces.is_empty() {
-        return None;
-    }
-
-    let big_val: bool = shared_rand.random_ratio(1, 5);
-
-    let index_rand = shared_rand.random_range(0..empty_spaces.len());
-    let row: usize = empty_spaces[index_rand] / N;
-    let col: usize = empty_spaces[index_rand] % N;
-
-    Some((col, row, if big_val { 2 } else { 1 }))
+    board: &Board<usize>,
+) -> Vec<(usize, usize, usize)> {
+    spawner
+        .spawn(board, &mut shared_rand.0)
+        .into_iter()
+        .map(|spawn| (spawn.col, spawn.row, spawn.value))
+        .collect()
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn produce_new_tile_system(
     mut commands: Commands,
+    layout: Res<BoardLayout>,
     mut shared_rand: ResMut<SharedRand>,
+    mut spawner: ResMut<SpawnerResource>,
     mut board_state: ResMut<BoardStateResource>,
+    mut forced_spawn: ResMut<ForcedSpawn>,
+    mut spawn_messages: MessageWriter<SpawnMessage>,
+    block_query: Query<&Value, With<Block>>,
 ) {
     let BoardStateResource(board) = board_state.as_mut();
 
-    let Some((col, row, val)) = acquire_empty_tile(shared_rand.as_mut(), board) else {
-        panic!("Shouldn't acquire empty tile after ending")
+    let tiles = match forced_spawn.0.take() {
+        Some(spawns) => spawns.into_iter().map(|spawn| (spawn.col, spawn.row, spawn.value)).collect(),
+        None => acquire_empty_tiles(spawner.as_mut(), shared_rand.as_mut(), &values_board(board, &block_query)),
     };
-    let entity = commands.spawn(produce_block_bundle(col, row, val)).id();
+    if tiles.is_empty() {
+        panic!("Shouldn't acquire empty tile after ending")
+    }
+
+    for (col, row, val) in tiles {
+        let entity = commands.spawn(produce_block_bundle(col, row, val, &layout)).id();
 
-    board[col + row * SIZE] = Some(entity);
-    trace!("produced new tile at [{}, {}] with value {}", col, row, val);
+        let index = col + row * board.width;
+        board[index] = Some(entity);
+        spawn_messages.write(SpawnMessage(TileSpawn { col, row, value: val }));
+        trace!("produced new tile at [{}, {}] with value {}", col, row, val);
+    }
 }
 
 pub fn collision_system(
+    layout: Res<BoardLayout>,
     collider_query: Query<(Entity, &Transform), With<Collider>>,
     mut collision_messages: MessageWriter<CollisionMessage>,
 ) {
@@ -194,7 +199,7 @@ pub fn collision_system(
             warn!("Collision tree has less then 2 nodes");
             continue;
         };
-        if closest_neighbor.1 < (RECT_SIZE * RECT_SIZE * COLLISION_EPSILON)
+        if closest_neighbor.1 < (layout.tile_size * layout.tile_size * COLLISION_EPSILON)
             && !processed.contains(&entity)
         {
             let higher;
@@ -245,6 +250,7 @@ fn get_direction(from: Vec3, to: Vec3) -> Option<Direction> {
 }
 
 pub fn process_collision_messages_system(
+    layout: Res<BoardLayout>,
     position_query: Query<&Transform, With<Collider>>,
     mut collision_messages: MessageReader<CollisionMessage>,
     mut merge_effect_message: MessageWriter<MergeEffectMessage>,
@@ -253,8 +259,8 @@ pub fn process_collision_messages_system(
         let p1 = position_query.get(collision_message.left).unwrap();
         let p2 = position_query.get(collision_message.right).unwrap();
 
-        let bound1 = translation_to_rect(p1.translation, RECT_SIZE);
-        let bound2 = translation_to_rect(p2.translation, RECT_SIZE);
+        let bound1 = translation_to_rect(p1.translation, layout.tile_size);
+        let bound2 = translation_to_rect(p2.translation, layout.tile_size);
         let collider_bound = bound1.intersect(bound2);
         let collider_center = collider_bound.center();
         let bound = match collision_message.direction {
@@ -276,9 +282,10 @@ pub fn process_collision_messages_system(
             ),
         };
 
+        // Effect is drawn in space of the block, which is scaled to the tile size
         merge_effect_message.write(MergeEffectMessage {
             entity: collision_message.right,
-            line: bound,
+            line: (bound.0 / layout.scale(), bound.1 / layout.scale()),
         });
     }
 }
@@ -291,56 +298,30 @@ mod tests {
     use rand_chacha::ChaCha8Rng;
 
     #[test]
-    fn rotate_test() {
-        let board_vec: Vec<Option<usize>> = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
-            .iter()
-            .map(|&x| Some(x))
-            .collect();
-        let full_board = rotate_board(&Board::<4, usize>(board_vec.clone()), RotateBy::Full);
+    fn lines_test() {
+        let board = Board::<usize>::new(4, 4);
         assert_eq!(
-            full_board,
-            Board::<4, usize>(
-                [15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0]
-                    .iter()
-                    .map(|&x| Some(x))
-                    .collect()
-            )
+            board.lines(&Direction::Left),
+            vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9, 10, 11], vec![12, 13, 14, 15]]
         );
-
-        let left_board = rotate_board(&Board::<4, usize>(board_vec.clone()), RotateBy::Left);
         assert_eq!(
-            left_board,
-            Board(
-                [3, 7, 11, 15, 2, 6, 10, 14, 1, 5, 9, 13, 0, 4, 8, 12]
-                    .iter()
-                    .map(|&x| Some(x))
-                    .collect()
-            )
+            board.lines(&Direction::Up),
+            vec![vec![0, 4, 8, 12], vec![1, 5, 9, 13], vec![2, 6, 10, 14], vec![3, 7, 11, 15]]
         );
-        let orig_board = rotate_board(&left_board, RotateBy::Right);
-        assert_eq!(orig_board, Board(board_vec.clone()));
 
-        let right_board = rotate_board(&Board::<4, usize>(board_vec.clone()), RotateBy::Right);
+        let board = Board::<usize>::new(3, 2);
+        assert_eq!(board.lines(&Direction::Right), vec![vec![2, 1, 0], vec![5, 4, 3]]);
         assert_eq!(
-            right_board,
-            Board(
-                [12, 8, 4, 0, 13, 9, 5, 1, 14, 10, 6, 2, 15, 11, 7, 3]
-                    .iter()
-                    .map(|&x| Some(x))
-                    .collect()
-            )
+            board.lines(&Direction::Down),
+            vec![vec![3, 0], vec![4, 1], vec![5, 2]]
         );
-        let orig_board = rotate_board(&right_board, RotateBy::Left);
-        assert_eq!(orig_board, Board(board_vec.clone()));
-    }
 
-    #[test]
-    fn rotate_index_test() {
-        let rotate_value = RotateBy::Left;
-        assert_eq!((0, 3), rotate_index::<4>(0, 0, rotate_value));
-        assert_eq!((0, 0), rotate_index::<4>(0, 3, rotate_value.revert()));
-
-        assert_eq!((1, 1), rotate_index::<4>(1, 2, RotateBy::Right));
+        let board = Board::<usize>::new(2, 5);
+        assert_eq!(
+            board.lines(&Direction::Left),
+            vec![vec![0, 1], vec![2, 3], vec![4, 5], vec![6, 7], vec![8, 9]]
+        );
+        assert_eq!(board.lines(&Direction::Up), vec![vec![0, 2, 4, 6, 8], vec![1, 3, 5, 7, 9]]);
     }
 
     #[test]
@@ -374,22 +355,23 @@ mod tests {
     }
 
     #[test]
-    fn acquire_empty_tile_test() {
+    fn acquire_empty_tiles_test() {
         // ChaCha8Rng does not seem to extend anything for us to abstract away. So we implement special deterministic Rand, and pray that test passes (put specific output)
+        let mut spawner = SpawnerResource::default();
         let mut rand = SharedRand(ChaCha8Rng::from_rng(&mut TestRand { seq: vec![0, 1, 2, 3], index: 0 }));
-        let board = Board::<2, Entity>(vec![None; 4]);
+        let board = Board::<usize>::new(2, 2);
 
-        assert_eq!(acquire_empty_tile(&mut rand, &board), Some((1, 0, 1)));
+        assert_eq!(acquire_empty_tiles(&mut spawner, &mut rand, &board), vec![(1, 0, 1)]);
 
         let mut rand = SharedRand(ChaCha8Rng::from_rng(&mut TestRand { seq: vec![3, 1, 2, 3], index: 0 }));
-        let board = Board::<2, Entity>(vec![None; 4]);
+        let board = Board::<usize>::new(2, 2);
 
-        assert_eq!(acquire_empty_tile(&mut rand, &board), Some((1, 0, 2)));
+        assert_eq!(acquire_empty_tiles(&mut spawner, &mut rand, &board), vec![(1, 0, 2)]);
 
         let mut rand = SharedRand(ChaCha8Rng::from_rng(&mut TestRand { seq: vec![3, 1, 2, 3], index: 0 }));
-        let board = Board::<2, Entity>(vec![Some(Entity::from_bits(rand.next_u64())); 4]);
+        let board = Board::from_cells(2, 2, vec![Some(rand.next_u64() as usize); 4]);
 
-        assert_eq!(acquire_empty_tile(&mut rand, &board), None);
+        assert_eq!(acquire_empty_tiles(&mut spawner, &mut rand, &board), vec![]);
     }
 
     struct TestRand {
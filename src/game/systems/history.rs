@@ -0,0 +1,141 @@
+use crate::game::components::*;
+use crate::game::mode::GameMode;
+use crate::game::states::*;
+use crate::game::systems::game_logic::*;
+use crate::SharedRand;
+use bevy::prelude::*;
+
+pub fn reset_history(mut history: ResMut<History>) {
+    *history = History::default();
+}
+
+/// Takes snapshot of the board at rest, previous snapshot goes to undo stack
+pub fn record_snapshot_system(
+    game_mode: Res<GameMode>,
+    board_state_resource: Res<BoardStateResource>,
+    score: Res<Score>,
+    moves: Res<Moves>,
+    shared_rand: Res<SharedRand>,
+    mut history: ResMut<History>,
+    block_query: Query<&Value, With<Block>>,
+) {
+    let snapshot = BoardSnapshot {
+        values: values_board(&board_state_resource.0, &block_query),
+        score: score.0,
+        moves: moves.0,
+        rng: shared_rand.0.clone(),
+    };
+
+    if let Some(previous) = history.current.replace(snapshot) {
+        history.undo.push_back(previous);
+        while history.undo.len() > game_mode.undo_depth() {
+            history.undo.pop_front();
+        }
+        history.redo.clear();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn history_system(
+    mut commands: Commands,
+    layout: Res<BoardLayout>,
+    mut history_messages: MessageReader<HistoryMessage>,
+    mut history: ResMut<History>,
+    mut board_state_resource: ResMut<BoardStateResource>,
+    mut score: ResMut<Score>,
+    mut moves: ResMut<Moves>,
+    mut shared_rand: ResMut<SharedRand>,
+    current_state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut loss_undone_messages: MessageWriter<LossUndoneMessage>,
+    block_query: Query<&Value, With<Block>>,
+) {
+    let History { current, undo, redo } = history.as_mut();
+    let mut restored = false;
+
+    // Walk the stacks first, board is restored once to the final snapshot
+    for message in history_messages.read() {
+        let target = match message {
+            HistoryMessage::Undo => undo.pop_back(),
+            HistoryMessage::Redo => redo.pop(),
+        };
+        let Some(target) = target else {
+            debug!("Nothing to {:?}", message);
+            continue;
+        };
+        let Some(present) = current.replace(target) else {
+            continue;
+        };
+
+        match message {
+            HistoryMessage::Undo => redo.push(present),
+            HistoryMessage::Redo => undo.push_back(present),
+        }
+        restored = true;
+    }
+
+    if !restored {
+        return;
+    }
+    let Some(snapshot) = current.as_ref() else {
+        return;
+    };
+
+    restore_board(&mut commands, &layout, &mut board_state_resource.0, &snapshot.values, &block_query);
+    score.0 = snapshot.score;
+    moves.0 = snapshot.moves;
+    shared_rand.0 = snapshot.rng.clone();
+    if *current_state.get() == GameState::Lose {
+        loss_undone_messages.write(LossUndoneMessage);
+    }
+    game_state.set(GameState::Wait);
+    trace!("Restored {}", board_state_resource.0);
+}
+
+/// Despawns blocks that differ from `values` and spawns blocks that are missing, blocks that match are kept
+pub fn restore_board(
+    commands: &mut Commands,
+    layout: &BoardLayout,
+    board: &mut Board<Entity>,
+    values: &Board<usize>,
+    block_query: &Query<&Value, With<Block>>,
+) {
+    let width = board.width;
+    for (index, &value) in values.iter().enumerate() {
+        let existing = board[index];
+        let existing_value = existing
+            .and_then(|entity| block_query.get(entity).ok())
+            .map(|value| value.0);
+        if existing_value == value {
+            continue;
+        }
+
+        if let Some(entity) = existing {
+            commands.entity(entity).despawn();
+        }
+        board[index] = value.map(|value| {
+            commands
+                .spawn(produce_block_bundle(index % width, index / width, value, layout))
+                .id()
+        });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn game_button_system(
+    interaction_query: Query<(&Interaction, &GameButtonAction), (Changed<Interaction>, With<Button>)>,
+    mut history_messages: MessageWriter<HistoryMessage>,
+) {
+    for (interaction, game_button_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match game_button_action {
+                GameButtonAction::Undo => {
+                    history_messages.write(HistoryMessage::Undo);
+                }
+                GameButtonAction::Redo => {
+                    history_messages.write(HistoryMessage::Redo);
+                }
+            }
+        }
+    }
+}
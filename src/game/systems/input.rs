@@ -1,28 +1,115 @@
 use bevy::prelude::*;
 use crate::game::components::*;
+use crate::game::controls::{Action, KeyBindings};
 use crate::game::states::*;
 
+/// Plays the move pressed now or, before it, the oldest move pressed while tiles were moving
 pub fn generate_direction_messages(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    input_buffer: Res<InputBuffer>,
+    mut input_queue: ResMut<InputQueue>,
     mut game_state: ResMut<NextState<GameState>>,
     mut direction_message: MessageWriter<DirectionMessage>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
-        direction_message.write(DirectionMessage(Direction::Left));
-    } else if keyboard_input.just_pressed(KeyCode::ArrowRight) {
-        direction_message.write(DirectionMessage(Direction::Right));
-    } else if keyboard_input.just_pressed(KeyCode::ArrowDown) {
-        direction_message.write(DirectionMessage(Direction::Down));
-    } else if keyboard_input.just_pressed(KeyCode::ArrowUp) {
-        direction_message.write(DirectionMessage(Direction::Up));
-    }
+    let pressed = key_bindings.pressed_direction(&keyboard_input);
+    let direction = match input_queue.0.pop_front() {
+        Some(queued) => {
+            if let Some(pressed) = pressed.filter(|_| input_queue.0.len() < input_buffer.0) {
+                input_queue.0.push_back(pressed);
+            }
+            Some(queued)
+        }
+        None => pressed,
+    };
 
-    if keyboard_input.any_just_pressed([
-        KeyCode::ArrowLeft,
-        KeyCode::ArrowRight,
-        KeyCode::ArrowDown,
-        KeyCode::ArrowUp,
-    ]) {
+    if let Some(direction) = direction {
+        direction_message.write(DirectionMessage(direction));
         game_state.set(GameState::Process);
     }
-}
\ No newline at end of file
+}
+
+/// Keeps moves pressed while tiles are moving, up to [InputBuffer], and snaps the movement with [SnapMoves]
+pub fn buffer_direction_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    input_buffer: Res<InputBuffer>,
+    snap_moves: Res<SnapMoves>,
+    mut input_queue: ResMut<InputQueue>,
+    mut queued_move_query: Query<&mut QueuedMove>,
+) {
+    if let Some(direction) = key_bindings.pressed_direction(&keyboard_input) {
+        buffer_move(direction, &input_buffer, &snap_moves, &mut input_queue, &mut queued_move_query);
+    }
+}
+
+/// Keeps the move for later if there is room and snaps the running movement with [SnapMoves]
+pub fn buffer_move(
+    direction: Direction,
+    input_buffer: &InputBuffer,
+    snap_moves: &SnapMoves,
+    input_queue: &mut InputQueue,
+    queued_move_query: &mut Query<&mut QueuedMove>,
+) {
+    if input_queue.0.len() < input_buffer.0 {
+        input_queue.0.push_back(direction);
+    }
+    if snap_moves.0 {
+        // Finished timers put tiles in place on the next tick of the movement
+        for mut queued_move in queued_move_query {
+            let duration = queued_move.1.duration();
+            queued_move.1.set_elapsed(duration);
+        }
+    }
+}
+
+/// Plays moves of touch, mouse and gamepad like move keys, buffered while tiles are moving
+#[allow(clippy::too_many_arguments)]
+pub fn move_input_system(
+    game_state: Res<State<GameState>>,
+    input_buffer: Res<InputBuffer>,
+    snap_moves: Res<SnapMoves>,
+    mut input_queue: ResMut<InputQueue>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut move_input_messages: MessageReader<MoveInputMessage>,
+    mut direction_messages: MessageWriter<DirectionMessage>,
+    mut queued_move_query: Query<&mut QueuedMove>,
+) {
+    for MoveInputMessage(direction) in move_input_messages.read().copied() {
+        // Key pressed in the same frame already made a move
+        let waiting = *game_state.get() == GameState::Wait && !matches!(*next_game_state, NextState::Pending(_));
+        if waiting {
+            direction_messages.write(DirectionMessage(direction));
+            next_game_state.set(GameState::Process);
+        } else {
+            buffer_move(direction, &input_buffer, &snap_moves, &mut input_queue, &mut queued_move_query);
+        }
+    }
+}
+
+/// Drops buffered moves, they were meant for the board as it was
+pub fn clear_input_queue(mut input_queue: ResMut<InputQueue>) {
+    input_queue.0.clear();
+}
+
+pub fn generate_history_messages(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut history_message: MessageWriter<HistoryMessage>,
+) {
+    if key_bindings.just_pressed(Action::Undo, &keyboard_input) {
+        history_message.write(HistoryMessage::Undo);
+    } else if key_bindings.just_pressed(Action::Redo, &keyboard_input) {
+        history_message.write(HistoryMessage::Redo);
+    }
+}
+
+pub fn generate_restart_messages(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut new_game_messages: MessageWriter<NewGameMessage>,
+) {
+    if key_bindings.just_pressed(Action::Restart, &keyboard_input) {
+        new_game_messages.write(NewGameMessage);
+    }
+}
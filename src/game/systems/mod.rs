@@ -2,4 +2,7 @@ pub mod input;
 pub mod process;
 pub mod movement;
 pub mod effect;
-pub mod game_logic;
\ No newline at end of file
+pub mod game_logic;
+pub mod history;
+pub mod score;
+pub mod overlay;
@@ -1,6 +1,5 @@
 use bevy::prelude::*;
 use crate::game::components::*;
-use crate::game::RECT_SIZE;
 use crate::game::states::*;
 use crate::game::utils::*;
 
@@ -16,7 +15,9 @@ pub fn queued_system_finished(
 
 pub fn queued_movement_system(
     time: Res<Time>,
+    layout: Res<BoardLayout>,
     mut commands: Commands,
+    mut merge_messages: MessageWriter<MergeMessage>,
     mut moving_block_query: Query<(Entity, &Value, &mut Transform, &mut QueuedMove)>,
 ) {
     let mut to_delete = Vec::new();
@@ -24,7 +25,7 @@ pub fn queued_movement_system(
     for (entity, value, mut transform, mut queued_move) in moving_block_query.iter_mut() {
         let QueuedMove(to, timer, merge_entity) = queued_move.as_mut();
 
-        let to_vec = vec2(col_to_x(to.0 as i32, RECT_SIZE), row_to_y(to.1 as i32, RECT_SIZE));
+        let to_vec = vec2(col_to_x(to.0 as i32, &layout), row_to_y(to.1 as i32, &layout));
 
         timer.tick(time.delta());
         if timer.is_finished() {
@@ -36,6 +37,7 @@ pub fn queued_movement_system(
             if let Some(merge_entity) = merge_entity {
                 to_delete.push(*merge_entity);
                 commands.entity(entity).insert(Value(value.0 + 1));
+                merge_messages.write(MergeMessage(value.0 + 1));
             }
         } else {
             let path = to_vec - transform.translation.xy();
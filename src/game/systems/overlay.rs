@@ -0,0 +1,237 @@
+use std::time::Duration;
+use bevy::prelude::*;
+use crate::game::components::*;
+use crate::game::mode::Endless;
+use crate::game::states::*;
+use crate::game::systems::game_logic::values_board;
+use crate::menu::AppState;
+
+/// Marker for entities of the overlay shown after win
+#[derive(Component)]
+pub struct OnWinOverlay;
+
+/// Marker for entities of the overlay shown after loss
+#[derive(Component)]
+pub struct OnLoseOverlay;
+
+/// Duration as minutes and seconds, with hours in front for long games
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// Lines about the finished game: score, biggest tile, moves and time
+fn game_summary(
+    board_state_resource: &BoardStateResource,
+    block_query: &Query<&Value, With<Block>>,
+    score: &Score,
+    moves: &Moves,
+    play_time: &PlayTime,
+) -> [String; 4] {
+    let values = values_board(&board_state_resource.0, block_query);
+    let max_value = values.iter().flatten().copied().max().unwrap_or(0);
+
+    [
+        format!("Score {}", score.0),
+        format!("Max tile {}", 1u64 << max_value),
+        format!("Moves {}", moves.0),
+        format!("Time {}", format_duration(play_time.0)),
+    ]
+}
+
+/// Overlay over the game screen with a title in the middle, `content` goes in a column under it
+pub fn spawn_overlay(
+    commands: &mut Commands,
+    marker: impl Bundle,
+    title: &str,
+    content: impl FnOnce(&mut ChildSpawnerCommands),
+) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.7)),
+            // Above the game screen UI
+            GlobalZIndex(1),
+            marker,
+            OnGameScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(title),
+                TextFont {
+                    font_size: 67.,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                Node {
+                    margin: UiRect::all(Val::Px(30.)),
+                    ..default()
+                },
+            ));
+            content(parent);
+        });
+}
+
+/// Button of an overlay, `action` tells what it does
+pub fn spawn_overlay_button(parent: &mut ChildSpawnerCommands, action: impl Component, text: &str) {
+    parent
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(300.),
+                height: Val::Px(65.),
+                margin: UiRect::all(Val::Px(10.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(text),
+                TextFont {
+                    font_size: 33.,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+            ));
+        });
+}
+
+/// Overlay of the finished game with its summary and buttons
+fn spawn_end_overlay(
+    commands: &mut Commands,
+    marker: impl Bundle,
+    title: &str,
+    summary: [String; 4],
+    actions: &[(OverlayButtonAction, &str)],
+) {
+    spawn_overlay(commands, marker, title, |parent| {
+        for line in summary {
+            parent.spawn((
+                Text::new(line),
+                TextFont {
+                    font_size: 25.,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.6, 0.6, 0.6)),
+            ));
+        }
+
+        for &(action, text) in actions {
+            spawn_overlay_button(parent, action, text);
+        }
+    });
+}
+
+pub fn win_overlay_setup(
+    mut commands: Commands,
+    board_state_resource: Res<BoardStateResource>,
+    score: Res<Score>,
+    moves: Res<Moves>,
+    play_time: Res<PlayTime>,
+    block_query: Query<&Value, With<Block>>,
+) {
+    let summary = game_summary(&board_state_resource, &block_query, &score, &moves, &play_time);
+    spawn_end_overlay(
+        &mut commands,
+        OnWinOverlay,
+        "You win!",
+        summary,
+        &[
+            (OverlayButtonAction::KeepGoing, "Keep going"),
+            (OverlayButtonAction::NewGame, "New game"),
+            (OverlayButtonAction::MainMenu, "Main menu"),
+            #[cfg(not(target_arch = "wasm32"))]
+            (OverlayButtonAction::Quit, "Quit"),
+        ],
+    );
+}
+
+pub fn lose_overlay_setup(
+    mut commands: Commands,
+    board_state_resource: Res<BoardStateResource>,
+    score: Res<Score>,
+    moves: Res<Moves>,
+    play_time: Res<PlayTime>,
+    block_query: Query<&Value, With<Block>>,
+) {
+    let summary = game_summary(&board_state_resource, &block_query, &score, &moves, &play_time);
+    spawn_end_overlay(
+        &mut commands,
+        OnLoseOverlay,
+        "Game over",
+        summary,
+        &[
+            // Overlay covers the undo button of the game screen
+            (OverlayButtonAction::Undo, "Undo"),
+            (OverlayButtonAction::NewGame, "New game"),
+            (OverlayButtonAction::MainMenu, "Main menu"),
+            #[cfg(not(target_arch = "wasm32"))]
+            (OverlayButtonAction::Quit, "Quit"),
+        ],
+    );
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn overlay_button_system(
+    interaction_query: Query<(&Interaction, &OverlayButtonAction), (Changed<Interaction>, With<Button>)>,
+    mut endless: ResMut<Endless>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut history_messages: MessageWriter<HistoryMessage>,
+    mut new_game_messages: MessageWriter<NewGameMessage>,
+    #[cfg(not(target_arch = "wasm32"))]
+    mut app_exit_messages: MessageWriter<AppExit>,
+) {
+    for (interaction, overlay_button_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match overlay_button_action {
+                OverlayButtonAction::KeepGoing => {
+                    endless.0 = true;
+                    game_state.set(GameState::Wait);
+                }
+                OverlayButtonAction::Undo => {
+                    history_messages.write(HistoryMessage::Undo);
+                }
+                OverlayButtonAction::NewGame => {
+                    new_game_messages.write(NewGameMessage);
+                }
+                OverlayButtonAction::MainMenu => {
+                    app_state.set(AppState::Menu);
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                OverlayButtonAction::Quit => {
+                    app_exit_messages.write(AppExit::Success);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_duration_test() {
+        assert_eq!(format_duration(Duration::ZERO), "0:00");
+        assert_eq!(format_duration(Duration::from_millis(65_900)), "1:05");
+        assert_eq!(format_duration(Duration::from_secs(3600 + 2 * 60 + 3)), "1:02:03");
+    }
+}
@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+use crate::game::components::*;
+use crate::game::stats::Statistics;
+
+pub fn reset_score(mut score: ResMut<Score>, mut moves: ResMut<Moves>, mut play_time: ResMut<PlayTime>) {
+    *score = Score::default();
+    *moves = Moves::default();
+    *play_time = PlayTime::default();
+}
+
+pub fn count_move_system(mut moves: ResMut<Moves>) {
+    moves.0 += 1;
+}
+
+pub fn play_time_system(time: Res<Time>, mut play_time: ResMut<PlayTime>) {
+    play_time.0 += time.delta();
+}
+
+/// Every merge adds face value of the merged block, value is stored as power of two
+pub fn score_system(
+    mut score: ResMut<Score>,
+    mut merge_messages: MessageReader<MergeMessage>,
+) {
+    for MergeMessage(value) in merge_messages.read() {
+        score.0 += 1u64 << value;
+    }
+}
+
+pub fn update_score_text(
+    score: Res<Score>,
+    mut score_text_query: Query<&mut Text, With<ScoreText>>,
+) {
+    for mut text in score_text_query.iter_mut() {
+        text.0 = score.0.to_string();
+    }
+}
+
+pub fn update_best_score_text(
+    statistics: Res<Statistics>,
+    mut best_score_text_query: Query<&mut Text, With<BestScoreText>>,
+) {
+    for mut text in best_score_text_query.iter_mut() {
+        text.0 = statistics.best_score.to_string();
+    }
+}
+
+pub fn update_seed_text(
+    game_seed: Res<GameSeed>,
+    mut seed_text_query: Query<&mut Text, With<SeedText>>,
+) {
+    for mut text in seed_text_query.iter_mut() {
+        text.0 = game_seed.0.to_string();
+    }
+}
@@ -1,12 +1,12 @@
-use crate::game::{X_SHIFT, Y_SHIFT};
+use crate::game::components::BoardLayout;
 use bevy::prelude::*;
 
-pub fn col_to_x(col: i32, size: f32) -> f32 {
-    col as f32 * size - X_SHIFT
+pub fn col_to_x(col: i32, layout: &BoardLayout) -> f32 {
+    layout.center.x + (col as f32 - (layout.width - 1) as f32 / 2.) * layout.tile_size
 }
 
-pub fn row_to_y(row: i32, size: f32) -> f32 {
-    -(row as f32) * size + Y_SHIFT
+pub fn row_to_y(row: i32, layout: &BoardLayout) -> f32 {
+    layout.center.y - (row as f32 - (layout.height - 1) as f32 / 2.) * layout.tile_size
 }
 
 pub fn translation_to_rect(translation: Vec3, size: f32) -> Rect {
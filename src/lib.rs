@@ -0,0 +1,26 @@
+pub mod animation_sprite;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+pub mod game;
+pub mod menu;
+pub mod storage;
+
+use bevy::prelude::*;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Resource, Deref, DerefMut)]
+pub struct SharedRand(pub ChaCha8Rng);
+impl Default for SharedRand {
+    fn default() -> Self {
+        let rng = if cfg!(target_arch = "wasm32") {
+            let mut seed = [0u8; 32];
+            getrandom::fill(&mut seed).expect("failed to fill seed");
+            ChaCha8Rng::from_seed(seed)
+        } else {
+            ChaCha8Rng::from_os_rng()
+        };
+
+        Self(rng)
+    }
+}
@@ -1,22 +1,26 @@
-mod animation_sprite;
-mod game;
-mod menu;
-
 use bevy::asset::AssetMetaCheck;
 use bevy::camera::Viewport;
 use bevy::camera::ScalingMode;
-use crate::game::effects;
 use bevy::post_process::bloom::Bloom;
 use bevy::core_pipeline::tonemapping::Tonemapping;
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy::render::view::Hdr;
-use rand_chacha::rand_core::SeedableRng;
-use rand_chacha::ChaCha8Rng;
-use crate::menu::{AppState, MenuState};
-use crate::menu::menu_mod::MenuButtonAction;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy_2048_p::cli;
+use bevy_2048_p::game::effects;
+use bevy_2048_p::game::playback::WatchReplay;
+use bevy_2048_p::game::save::ContinueGame;
+use bevy_2048_p::game::SeedSetting;
+use bevy_2048_p::menu::{AppState, MenuState};
+use bevy_2048_p::menu::menu_mod::MenuButtonAction;
+use bevy_2048_p::{animation_sprite, game, menu, storage, SharedRand};
 
 fn main() {
+    // Parsed before anything else, so `--help` and invalid arguments exit without opening a window
+    #[cfg(not(target_arch = "wasm32"))]
+    let cli_args = cli::CliArgs::from_env();
+
     // this code is compiled only if debug assertions are enabled (debug mode)
     #[cfg(debug_assertions)]
     let log_plugin = LogPlugin {
@@ -43,34 +47,24 @@ fn main() {
             ..default()
         });
 
-    App::new()
-        .add_plugins((
-            default_plugins,
-            menu::main_menu_plugin,
-            game::game_plugin,
-            animation_sprite::animate_sprite_plugin,
-            effects::effects_plugin,
-        ))
-        .add_systems(Startup, camera_setup)
-        .insert_resource(SharedRand::default())
-        .add_systems(Update, menu_action)
-        .run();
-}
+    let mut app = App::new();
+    app.add_plugins((
+        default_plugins,
+        storage::storage_plugin,
+        menu::main_menu_plugin,
+        game::game_plugin,
+        animation_sprite::animate_sprite_plugin,
+        effects::effects_plugin,
+    ))
+    .add_systems(Startup, camera_setup)
+    .insert_resource(SharedRand::default())
+    .add_systems(Update, menu_action);
 
-#[derive(Resource, Deref, DerefMut)]
-struct SharedRand(ChaCha8Rng);
-impl Default for SharedRand {
-    fn default() -> Self {
-        let rng = if cfg!(target_arch = "wasm32") {
-            let mut seed = [0u8; 32];
-            getrandom::fill(&mut seed).expect("failed to fill seed");
-            ChaCha8Rng::from_seed(seed)
-        } else {
-            ChaCha8Rng::from_os_rng()
-        };
+    // Command line overrides settings for new games
+    #[cfg(not(target_arch = "wasm32"))]
+    cli_args.insert_resources(&mut app);
 
-        Self(rng)
-    }
+    app.run();
 }
 
 fn camera_setup(
@@ -102,6 +96,7 @@ fn camera_setup(
     ));
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn menu_action(
     interaction_query: Query<
         (&Interaction, &MenuButtonAction),
@@ -110,6 +105,10 @@ fn menu_action(
     mut app_exit_events: MessageWriter<AppExit>,
     mut menu_state: ResMut<NextState<MenuState>>,
     mut game_state: ResMut<NextState<AppState>>,
+    mut continue_game: ResMut<ContinueGame>,
+    mut watch_replay: ResMut<WatchReplay>,
+    mut seed_setting: ResMut<SeedSetting>,
+    app_state: Res<State<AppState>>,
 ) {
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
@@ -117,14 +116,50 @@ fn menu_action(
                 MenuButtonAction::Quit => {
                     app_exit_events.write(AppExit::Success);
                 }
+                MenuButtonAction::Continue => {
+                    continue_game.0 = true;
+                    watch_replay.0 = false;
+                    game_state.set(AppState::Game);
+                    menu_state.set(MenuState::Disabled);
+                }
                 MenuButtonAction::Play => {
+                    continue_game.0 = false;
+                    watch_replay.0 = false;
                     game_state.set(AppState::Game);
                     menu_state.set(MenuState::Disabled);
                 }
+                MenuButtonAction::WatchReplay => {
+                    continue_game.0 = false;
+                    watch_replay.0 = true;
+                    game_state.set(AppState::Game);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::NewGame => menu_state.set(MenuState::NewGame),
+                MenuButtonAction::RandomSeed => seed_setting.0 = None,
                 MenuButtonAction::Settings => menu_state.set(MenuState::Settings),
+                MenuButtonAction::Statistics => menu_state.set(MenuState::Statistics),
                 MenuButtonAction::SettingsSound => {
                     menu_state.set(MenuState::SettingsSound);
                 }
+                MenuButtonAction::SettingsMode => {
+                    menu_state.set(MenuState::SettingsMode);
+                }
+                MenuButtonAction::SettingsTarget => {
+                    menu_state.set(MenuState::SettingsTarget);
+                }
+                MenuButtonAction::SettingsBoard => {
+                    menu_state.set(MenuState::SettingsBoard);
+                }
+                MenuButtonAction::SettingsInput => {
+                    menu_state.set(MenuState::SettingsInput);
+                }
+                MenuButtonAction::SettingsControls => {
+                    menu_state.set(MenuState::SettingsControls);
+                }
+                // Settings opened from pause go back to the paused game
+                MenuButtonAction::BackToMainMenu if *app_state.get() == AppState::Game => {
+                    menu_state.set(MenuState::Disabled);
+                }
                 MenuButtonAction::BackToMainMenu => menu_state.set(MenuState::Main),
                 MenuButtonAction::BackToSettings => {
                     menu_state.set(MenuState::Settings);
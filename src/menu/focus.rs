@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use bevy::ui::{UiGlobalTransform, UiSystems};
+use crate::game::gamepad::gamepad_direction;
+use crate::game::{Direction, GameState, PauseState};
+use crate::menu::menu_mod::MenuButtonAction;
+use crate::menu::AppState;
+
+const FOCUS_OUTLINE_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+
+/// Button chosen with a gamepad, pressed with the south button
+#[derive(Resource, Default)]
+pub struct MenuFocus(pub Option<Entity>);
+
+pub fn focus_plugin(app: &mut App) {
+    app.init_resource::<MenuFocus>().add_systems(
+        PreUpdate,
+        // After interactions of the mouse, so gamepad presses aren't overwritten and
+        // every system of the frame sees them. Overlays of the finished and the paused game have buttons as well
+        focus_navigation_system.after(UiSystems::Focus).run_if(
+            in_state(AppState::Menu)
+                .or(in_state(GameState::Win))
+                .or(in_state(GameState::Lose))
+                .or(in_state(PauseState::Paused)),
+        ),
+    );
+}
+
+/// Button next to `from` towards `direction`, closer along the direction and less off to the side is better
+///
+/// Positions are in window coordinates with y going down.
+pub fn next_focus(from: Vec2, direction: Direction, buttons: &[(Entity, Vec2)]) -> Option<Entity> {
+    let towards = match direction {
+        Direction::Left => Vec2::NEG_X,
+        Direction::Right => Vec2::X,
+        Direction::Up => Vec2::NEG_Y,
+        Direction::Down => Vec2::Y,
+    };
+
+    buttons
+        .iter()
+        .filter_map(|&(entity, position)| {
+            let offset = position - from;
+            let along = offset.dot(towards);
+            // Tiny steps are buttons of the same row or column
+            (along > 1.).then(|| (entity, along + 2. * offset.perp_dot(towards).abs()))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+/// D-pad and left stick move the focus between visible buttons, south button presses the focused one
+/// and east button presses "Back"
+#[allow(clippy::type_complexity)]
+fn focus_navigation_system(
+    mut commands: Commands,
+    gamepads: Query<&Gamepad>,
+    mut held: Local<Option<Direction>>,
+    mut pressed: Local<Option<Entity>>,
+    mut focus: ResMut<MenuFocus>,
+    mut button_query: Query<
+        (Entity, &UiGlobalTransform, &InheritedVisibility, &mut Interaction, Option<&MenuButtonAction>),
+        With<Button>,
+    >,
+) {
+    // Press of the last frame was seen by every system already
+    if let Some(mut interaction) = pressed.take().and_then(|entity| button_query.get_mut(entity).ok()).map(|button| button.3) {
+        if *interaction == Interaction::Pressed {
+            *interaction = Interaction::None;
+        }
+    }
+    // Focused button is gone with its screen
+    if focus.0.is_some_and(|entity| !button_query.contains(entity)) {
+        focus.0 = None;
+    }
+
+    if let Some(direction) = gamepad_direction(gamepads, &mut held) {
+        let buttons: Vec<(Entity, Vec2)> = button_query
+            .iter()
+            .filter(|(_, _, visibility, ..)| visibility.get())
+            .map(|(entity, transform, ..)| (entity, transform.translation))
+            .collect();
+        let from = focus.0.and_then(|entity| buttons.iter().find(|(button, _)| *button == entity));
+        let next = match from {
+            Some(&(_, position)) => next_focus(position, direction, &buttons),
+            // First move focuses the top left button
+            None => buttons
+                .iter()
+                .min_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)))
+                .map(|(entity, _)| *entity),
+        };
+
+        if let Some(next) = next {
+            if let Some(previous) = focus.0 {
+                commands.entity(previous).remove::<Outline>();
+            }
+            commands.entity(next).insert(Outline::new(Val::Px(4.0), Val::ZERO, FOCUS_OUTLINE_COLOR));
+            focus.0 = Some(next);
+        }
+    }
+
+    let confirm = gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::South));
+    let back = gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::East));
+    let target = if confirm {
+        focus.0
+    } else if back {
+        button_query
+            .iter()
+            .find(|(.., action)| {
+                matches!(action, Some(MenuButtonAction::BackToMainMenu | MenuButtonAction::BackToSettings))
+            })
+            .map(|(entity, ..)| entity)
+    } else {
+        None
+    };
+
+    if let Some(mut interaction) = target.and_then(|entity| button_query.get_mut(entity).ok()).map(|button| button.3) {
+        *interaction = Interaction::Pressed;
+        *pressed = target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_focus_test() {
+        let entity = Entity::from_raw_u32;
+        // Column of two buttons with a row of three under them
+        let buttons = [
+            (entity(1).unwrap(), Vec2::new(100., 0.)),
+            (entity(2).unwrap(), Vec2::new(100., 100.)),
+            (entity(3).unwrap(), Vec2::new(0., 200.)),
+            (entity(4).unwrap(), Vec2::new(100., 200.)),
+            (entity(5).unwrap(), Vec2::new(200., 200.)),
+        ];
+
+        assert_eq!(next_focus(buttons[0].1, Direction::Down, &buttons), Some(buttons[1].0));
+        assert_eq!(next_focus(buttons[1].1, Direction::Down, &buttons), Some(buttons[3].0));
+        assert_eq!(next_focus(buttons[3].1, Direction::Left, &buttons), Some(buttons[2].0));
+        assert_eq!(next_focus(buttons[2].1, Direction::Up, &buttons), Some(buttons[1].0));
+        assert_eq!(next_focus(buttons[0].1, Direction::Up, &buttons), None);
+        assert_eq!(next_focus(buttons[4].1, Direction::Right, &buttons), None);
+    }
+}
@@ -1,11 +1,22 @@
 use bevy::{color::palettes::css::CRIMSON, prelude::*};
+use bevy::input::keyboard::{Key, KeyboardInput};
 
 use super::{despawn_screen, AppState, MenuState, Volume, TEXT_COLOR};
+use crate::game::controls::{key_name, Action, BindingConflict, ControlsPreset, KeyBindings};
+use crate::game::mode::GameMode;
+use crate::game::playback::LoadedReplay;
+use crate::game::rules::WinTarget;
+use crate::game::save::SavedGameResource;
+use crate::game::stats::Statistics;
+use crate::game::{BoardSize, InputBuffer, SeedSetting, SnapMoves, MAX_BOARD_SIZE, MAX_INPUT_BUFFER, MIN_BOARD_SIZE};
 
-// This plugin manages the menu, with 5 different screens:
-// - a main menu with "New Game", "Settings", "Quit"
-// - a settings menu with two submenus and a back button
-// - two settings screen with a setting that can be set and a back button
+// This plugin manages the menu, with 10 different screens:
+// - a main menu with "Continue", "New Game", "Watch replay", "Statistics", "Settings", "Quit"
+// - a new game dialog with a seed field, a start button and a back button
+// - a statistics screen with best score, lifetime statistics and a back button
+// - a settings menu with six submenus and a back button
+// - five settings screen with a setting that can be set and a back button
+// - a controls screen with presets, a key for every action and a back button
 pub fn menu_plugin(app: &mut App) {
     app
         // At start, the menu is not enabled. This will be changed in `menu_setup` when
@@ -16,6 +27,24 @@ pub fn menu_plugin(app: &mut App) {
         // Systems to handle the main menu screen
         .add_systems(OnEnter(MenuState::Main), main_menu_setup)
         .add_systems(OnExit(MenuState::Main), despawn_screen::<OnMainMenuScreen>)
+        // Systems to handle the new game dialog
+        .add_systems(OnEnter(MenuState::NewGame), new_game_menu_setup)
+        .add_systems(
+            Update,
+            (
+                seed_input_system,
+                update_seed_input_text.run_if(resource_changed::<SeedSetting>),
+            )
+                .chain()
+                .run_if(in_state(MenuState::NewGame)),
+        )
+        .add_systems(OnExit(MenuState::NewGame), despawn_screen::<OnNewGameMenuScreen>)
+        // Systems to handle the statistics screen
+        .add_systems(OnEnter(MenuState::Statistics), statistics_menu_setup)
+        .add_systems(
+            OnExit(MenuState::Statistics),
+            despawn_screen::<OnStatisticsMenuScreen>,
+        )
         // Systems to handle the settings menu screen
         .add_systems(OnEnter(MenuState::Settings), settings_menu_setup)
         .add_systems(
@@ -32,10 +61,69 @@ pub fn menu_plugin(app: &mut App) {
             OnExit(MenuState::SettingsSound),
             despawn_screen::<OnSoundSettingsMenuScreen>,
         )
+        // Systems to handle the game mode settings screen
+        .add_systems(OnEnter(MenuState::SettingsMode), mode_settings_menu_setup)
+        .add_systems(
+            Update,
+            setting_button::<GameMode>.run_if(in_state(MenuState::SettingsMode)),
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsMode),
+            despawn_screen::<OnModeSettingsMenuScreen>,
+        )
+        // Systems to handle the win target settings screen
+        .add_systems(OnEnter(MenuState::SettingsTarget), target_settings_menu_setup)
+        .add_systems(
+            Update,
+            setting_button::<WinTarget>.run_if(in_state(MenuState::SettingsTarget)),
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsTarget),
+            despawn_screen::<OnTargetSettingsMenuScreen>,
+        )
+        // Systems to handle the board settings screen
+        .add_systems(OnEnter(MenuState::SettingsBoard), board_settings_menu_setup)
+        .add_systems(
+            Update,
+            setting_button::<BoardSize>.run_if(in_state(MenuState::SettingsBoard)),
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsBoard),
+            despawn_screen::<OnBoardSettingsMenuScreen>,
+        )
+        // Systems to handle the input settings screen
+        .add_systems(OnEnter(MenuState::SettingsInput), input_settings_menu_setup)
+        .add_systems(
+            Update,
+            (setting_button::<InputBuffer>, setting_button::<SnapMoves>)
+                .run_if(in_state(MenuState::SettingsInput)),
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsInput),
+            despawn_screen::<OnInputSettingsMenuScreen>,
+        )
+        // Systems to handle the controls settings screen
+        .init_resource::<BindingCapture>()
+        .add_systems(OnEnter(MenuState::SettingsControls), controls_settings_menu_setup)
+        .add_systems(
+            Update,
+            (
+                controls_button_system,
+                capture_key_system.run_if(|capture: Res<BindingCapture>| capture.0.is_some()),
+                update_binding_text.run_if(resource_changed::<KeyBindings>.or(resource_changed::<BindingCapture>)),
+            )
+                .chain()
+                .run_if(in_state(MenuState::SettingsControls)),
+        )
+        .add_systems(
+            OnExit(MenuState::SettingsControls),
+            despawn_screen::<OnControlsSettingsMenuScreen>,
+        )
         // Common systems to all screens that handles buttons behavior
         .add_systems(
             Update,
-            button_system.run_if(in_state(AppState::Menu)),
+            // Settings are also opened from the paused game
+            button_system.run_if(in_state(AppState::Menu).or(not(in_state(MenuState::Disabled)))),
         );
 }
 
@@ -43,6 +131,18 @@ pub fn menu_plugin(app: &mut App) {
 #[derive(Component)]
 struct OnMainMenuScreen;
 
+// Tag component used to tag entities added on the new game dialog
+#[derive(Component)]
+struct OnNewGameMenuScreen;
+
+// Tag component used to mark the text of the seed field
+#[derive(Component)]
+struct SeedInputText;
+
+// Tag component used to tag entities added on the statistics screen
+#[derive(Component)]
+struct OnStatisticsMenuScreen;
+
 // Tag component used to tag entities added on the settings menu screen
 #[derive(Component)]
 struct OnSettingsMenuScreen;
@@ -51,6 +151,42 @@ struct OnSettingsMenuScreen;
 #[derive(Component)]
 struct OnSoundSettingsMenuScreen;
 
+// Tag component used to tag entities added on the game mode settings menu screen
+#[derive(Component)]
+struct OnModeSettingsMenuScreen;
+
+// Tag component used to tag entities added on the win target settings menu screen
+#[derive(Component)]
+struct OnTargetSettingsMenuScreen;
+
+// Tag component used to tag entities added on the board settings menu screen
+#[derive(Component)]
+struct OnBoardSettingsMenuScreen;
+
+// Tag component used to tag entities added on the input settings menu screen
+#[derive(Component)]
+struct OnInputSettingsMenuScreen;
+
+// Tag component used to tag entities added on the controls settings menu screen
+#[derive(Component)]
+struct OnControlsSettingsMenuScreen;
+
+// Button that waits for a new key of the action when pressed
+#[derive(Component)]
+struct BindingButton(Action);
+
+// Text with the key of the action
+#[derive(Component)]
+struct BindingText(Action);
+
+// Tag component used to mark the text telling what happens with the pressed key
+#[derive(Component)]
+struct ControlsStatusText;
+
+// Action waiting for its new key on the controls settings screen
+#[derive(Resource, Default)]
+struct BindingCapture(Option<Action>);
+
 const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
 const HOVERED_PRESSED_BUTTON: Color = Color::srgb(0.25, 0.65, 0.25);
@@ -63,15 +199,26 @@ struct SelectedOption;
 // All actions that can be triggered from a button click
 #[derive(Component)]
 pub enum MenuButtonAction {
+    Continue,
+    NewGame,
     Play,
+    RandomSeed,
+    WatchReplay,
+    Statistics,
     Settings,
     SettingsSound,
+    SettingsMode,
+    SettingsTarget,
+    SettingsBoard,
+    SettingsInput,
+    SettingsControls,
     BackToMainMenu,
     BackToSettings,
     Quit,
 }
 
 // This system handles changing all buttons color based on mouse interaction
+#[allow(clippy::type_complexity)]
 fn button_system(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, Option<&SelectedOption>),
@@ -89,10 +236,11 @@ fn button_system(
 }
 
 // This system updates the settings when a new value for a setting is selected, and marks
-// the button as the one currently selected
+// the button as the one currently selected, among the buttons of the same setting
+#[allow(clippy::type_complexity)]
 fn setting_button<T: Resource + Component + PartialEq + Copy>(
     interaction_query: Query<(&Interaction, &T, Entity), (Changed<Interaction>, With<Button>)>,
-    selected_query: Single<(Entity, &mut BackgroundColor), With<SelectedOption>>,
+    selected_query: Single<(Entity, &mut BackgroundColor), (With<SelectedOption>, With<T>)>,
     mut commands: Commands,
     mut setting: ResMut<T>,
 ) {
@@ -111,7 +259,12 @@ fn menu_setup(mut menu_state: ResMut<NextState<MenuState>>) {
     menu_state.set(MenuState::Main);
 }
 
-fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn main_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    saved_game: Res<SavedGameResource>,
+    loaded_replay: Res<LoadedReplay>,
+) {
     // Common style for all buttons on the screen
     let button_node = Node {
         width: Val::Px(300.0),
@@ -170,16 +323,35 @@ fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                         },
                     ));
 
-                    // Display three buttons for each action available from the main menu:
+                    // Display buttons for each action available from the main menu:
+                    // - continue, when there is a saved game
                     // - new game
+                    // - watch replay, when there is a replay to watch
+                    // - statistics
                     // - settings
                     // - quit
+                    if saved_game.0.is_some() {
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Continue,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new("Continue"),
+                                    button_text_font.clone(),
+                                    TextColor(TEXT_COLOR),
+                                ));
+                            });
+                    }
                     parent
                         .spawn((
                             Button,
                             button_node.clone(),
                             BackgroundColor(NORMAL_BUTTON),
-                            MenuButtonAction::Play,
+                            MenuButtonAction::NewGame,
                         ))
                         .with_children(|parent| {
                             let icon = asset_server.load("textures/right.png");
@@ -190,6 +362,36 @@ fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                                 TextColor(TEXT_COLOR),
                             ));
                         });
+                    if loaded_replay.0.is_some() {
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::WatchReplay,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new("Watch replay"),
+                                    button_text_font.clone(),
+                                    TextColor(TEXT_COLOR),
+                                ));
+                            });
+                    }
+                    parent
+                        .spawn((
+                            Button,
+                            button_node.clone(),
+                            BackgroundColor(NORMAL_BUTTON),
+                            MenuButtonAction::Statistics,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                Text::new("Statistics"),
+                                button_text_font.clone(),
+                                TextColor(TEXT_COLOR),
+                            ));
+                        });
                     parent
                         .spawn((
                             Button,
@@ -227,7 +429,129 @@ fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         });
 }
 
-fn settings_menu_setup(mut commands: Commands) {
+fn seed_input_text(seed_setting: &SeedSetting) -> String {
+    match seed_setting.0 {
+        Some(seed) => seed.to_string(),
+        None => "Random".to_string(),
+    }
+}
+
+fn new_game_menu_setup(mut commands: Commands, seed_setting: Res<SeedSetting>) {
+    let button_node = Node {
+        width: Val::Px(300.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(20.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = (
+        TextFont {
+            font_size: 33.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    );
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnNewGameMenuScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    BackgroundColor(CRIMSON.into()),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((Text::new("Seed (type digits)"), button_text_style.clone()));
+                    // Seed field, edited with the keyboard by `seed_input_system`
+                    parent
+                        .spawn((
+                            Node {
+                                width: Val::Px(500.0),
+                                height: Val::Px(65.0),
+                                margin: UiRect::all(Val::Px(20.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(NORMAL_BUTTON),
+                        ))
+                        .with_child((
+                            Text::new(seed_input_text(&seed_setting)),
+                            button_text_style.clone(),
+                            SeedInputText,
+                        ));
+                    for (action, text) in [
+                        (MenuButtonAction::Play, "Start"),
+                        (MenuButtonAction::RandomSeed, "Random seed"),
+                        (MenuButtonAction::BackToMainMenu, "Back"),
+                    ] {
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                action,
+                            ))
+                            .with_child((Text::new(text), button_text_style.clone()));
+                    }
+                });
+        });
+}
+
+// This system edits the seed with typed digits, backspace removes the last digit
+fn seed_input_system(
+    mut keyboard_input_reader: MessageReader<KeyboardInput>,
+    mut seed_setting: ResMut<SeedSetting>,
+) {
+    for keyboard_input in keyboard_input_reader.read() {
+        if !keyboard_input.state.is_pressed() {
+            continue;
+        }
+
+        match &keyboard_input.logical_key {
+            Key::Character(character) => {
+                let Some(digit) = character.chars().next().and_then(|c| c.to_digit(10)) else {
+                    continue;
+                };
+                // Digits that would overflow the seed are ignored
+                let seed = seed_setting.0.unwrap_or(0);
+                if let Some(seed) = seed.checked_mul(10).and_then(|seed| seed.checked_add(digit as u64)) {
+                    seed_setting.0 = Some(seed);
+                }
+            }
+            Key::Backspace => {
+                seed_setting.0 = seed_setting.0.map(|seed| seed / 10).filter(|&seed| seed > 0);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_seed_input_text(
+    seed_setting: Res<SeedSetting>,
+    mut seed_input_query: Query<&mut Text, With<SeedInputText>>,
+) {
+    for mut text in &mut seed_input_query {
+        text.0 = seed_input_text(&seed_setting);
+    }
+}
+
+fn statistics_menu_setup(mut commands: Commands, statistics: Res<Statistics>) {
     let button_node = Node {
         width: Val::Px(200.0),
         height: Val::Px(65.0),
@@ -236,6 +560,86 @@ fn settings_menu_setup(mut commands: Commands) {
         align_items: AlignItems::Center,
         ..default()
     };
+    let button_text_style = (
+        TextFont {
+            font_size: 33.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    );
+
+    let highest_tile = if statistics.highest_value > 0 {
+        (1u64 << statistics.highest_value).to_string()
+    } else {
+        "-".to_string()
+    };
+    let rows = [
+        ("Best score", statistics.best_score.to_string()),
+        ("Games played", statistics.games_played.to_string()),
+        ("Wins", statistics.wins.to_string()),
+        ("Losses", statistics.losses.to_string()),
+        ("Highest tile", highest_tile),
+        ("Total merges", statistics.total_merges.to_string()),
+        ("Total moves", statistics.total_moves.to_string()),
+    ];
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnStatisticsMenuScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    BackgroundColor(CRIMSON.into()),
+                ))
+                .with_children(|parent| {
+                    for (label, value) in rows {
+                        parent
+                            .spawn(Node {
+                                width: Val::Px(500.0),
+                                justify_content: JustifyContent::SpaceBetween,
+                                margin: UiRect::all(Val::Px(5.0)),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn((Text::new(label), button_text_style.clone()));
+                                parent.spawn((Text::new(value), button_text_style.clone()));
+                            });
+                    }
+                    parent
+                        .spawn((
+                            Button,
+                            button_node,
+                            BackgroundColor(NORMAL_BUTTON),
+                            MenuButtonAction::BackToMainMenu,
+                        ))
+                        .with_child((Text::new("Back"), button_text_style));
+                });
+        });
+}
+
+fn settings_menu_setup(mut commands: Commands) {
+    let button_node = Node {
+        width: Val::Px(200.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
 
     let button_text_style = (
         TextFont {
@@ -269,6 +673,11 @@ fn settings_menu_setup(mut commands: Commands) {
                 .with_children(|parent| {
                     for (action, text) in [
                         (MenuButtonAction::SettingsSound, "Sound"),
+                        (MenuButtonAction::SettingsMode, "Mode"),
+                        (MenuButtonAction::SettingsTarget, "Target"),
+                        (MenuButtonAction::SettingsBoard, "Board"),
+                        (MenuButtonAction::SettingsInput, "Input"),
+                        (MenuButtonAction::SettingsControls, "Controls"),
                         (MenuButtonAction::BackToMainMenu, "Back"),
                     ] {
                         parent
@@ -361,4 +770,536 @@ fn sound_settings_menu_setup(mut commands: Commands, volume: Res<Volume>) {
                         .with_child((Text::new("Back"), button_text_style));
                 });
         });
-}
\ No newline at end of file
+}
+
+fn mode_settings_menu_setup(mut commands: Commands, game_mode: Res<GameMode>) {
+    let button_node = Node {
+        width: Val::Px(200.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(20.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = (
+        TextFont {
+            font_size: 33.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    );
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnModeSettingsMenuScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(CRIMSON.into()),
+                ))
+                .with_children(|parent| {
+                    parent
+                        .spawn((
+                            Node {
+                                align_items: AlignItems::Center,
+                                justify_content: JustifyContent::Center,
+                                // Buttons of every mode don't fit in one row of small windows
+                                flex_wrap: FlexWrap::Wrap,
+                                max_width: Val::Vw(90.0),
+                                ..default()
+                            },
+                            BackgroundColor(CRIMSON.into()),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((Text::new("Mode"), button_text_style.clone()));
+                            for mode_setting in GameMode::ALL {
+                                let mut entity = parent.spawn((
+                                    Button,
+                                    button_node.clone(),
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    mode_setting,
+                                ));
+                                entity.with_child((
+                                    Text::new(mode_setting.name()),
+                                    button_text_style.clone(),
+                                ));
+                                if *game_mode == mode_setting {
+                                    entity.insert(SelectedOption);
+                                }
+                            }
+                        });
+                    parent
+                        .spawn((
+                            Button,
+                            button_node,
+                            BackgroundColor(NORMAL_BUTTON),
+                            MenuButtonAction::BackToSettings,
+                        ))
+                        .with_child((Text::new("Back"), button_text_style));
+                });
+        });
+}
+
+fn target_settings_menu_setup(mut commands: Commands, win_target: Res<WinTarget>) {
+    let button_node = Node {
+        width: Val::Px(200.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(20.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = (
+        TextFont {
+            font_size: 33.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    );
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnTargetSettingsMenuScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(CRIMSON.into()),
+                ))
+                .with_children(|parent| {
+                    parent
+                        .spawn((
+                            Node {
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(CRIMSON.into()),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((Text::new("Target"), button_text_style.clone()));
+                            for target_setting in WinTarget::ALL {
+                                let mut entity = parent.spawn((
+                                    Button,
+                                    button_node.clone(),
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    target_setting,
+                                ));
+                                entity.with_child((
+                                    Text::new(target_setting.name()),
+                                    button_text_style.clone(),
+                                ));
+                                if *win_target == target_setting {
+                                    entity.insert(SelectedOption);
+                                }
+                            }
+                        });
+                    parent
+                        .spawn((
+                            Button,
+                            button_node,
+                            BackgroundColor(NORMAL_BUTTON),
+                            MenuButtonAction::BackToSettings,
+                        ))
+                        .with_child((Text::new("Back"), button_text_style));
+                });
+        });
+}
+
+fn board_settings_menu_setup(mut commands: Commands, board_size: Res<BoardSize>) {
+    let button_node = Node {
+        width: Val::Px(200.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(20.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = (
+        TextFont {
+            font_size: 33.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    );
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnBoardSettingsMenuScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(CRIMSON.into()),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((Text::new("Board size"), button_text_style.clone()));
+                    // One row of widths for every height
+                    for height in MIN_BOARD_SIZE..=MAX_BOARD_SIZE {
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                for width in MIN_BOARD_SIZE..=MAX_BOARD_SIZE {
+                                    let size_setting = BoardSize { width, height };
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(80.0),
+                                            height: Val::Px(50.0),
+                                            margin: UiRect::all(Val::Px(4.0)),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        size_setting,
+                                    ));
+                                    entity.with_child((
+                                        Text::new(format!("{width}x{height}")),
+                                        TextFont {
+                                            font_size: 24.0,
+                                            ..default()
+                                        },
+                                        TextColor(TEXT_COLOR),
+                                    ));
+                                    if *board_size == size_setting {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+                    }
+                    parent
+                        .spawn((
+                            Button,
+                            button_node,
+                            BackgroundColor(NORMAL_BUTTON),
+                            MenuButtonAction::BackToSettings,
+                        ))
+                        .with_child((Text::new("Back"), button_text_style));
+                });
+        });
+}
+
+fn input_settings_menu_setup(
+    mut commands: Commands,
+    input_buffer: Res<InputBuffer>,
+    snap_moves: Res<SnapMoves>,
+) {
+    let button_node = Node {
+        width: Val::Px(200.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(20.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = (
+        TextFont {
+            font_size: 33.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    );
+    let row_node = Node {
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnInputSettingsMenuScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(CRIMSON.into()),
+                ))
+                .with_children(|parent| {
+                    // Moves kept while tiles are moving
+                    parent
+                        .spawn((row_node.clone(), BackgroundColor(CRIMSON.into())))
+                        .with_children(|parent| {
+                            parent.spawn((Text::new("Buffer"), button_text_style.clone()));
+                            for buffer in 0..=MAX_INPUT_BUFFER {
+                                let buffer_setting = InputBuffer(buffer);
+                                let mut entity = parent.spawn((
+                                    Button,
+                                    Node {
+                                        width: Val::Px(80.0),
+                                        ..button_node.clone()
+                                    },
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    buffer_setting,
+                                ));
+                                entity.with_child((Text::new(buffer.to_string()), button_text_style.clone()));
+                                if *input_buffer == buffer_setting {
+                                    entity.insert(SelectedOption);
+                                }
+                            }
+                        });
+                    // Input during movement finishes it
+                    parent
+                        .spawn((row_node, BackgroundColor(CRIMSON.into())))
+                        .with_children(|parent| {
+                            parent.spawn((Text::new("Snap"), button_text_style.clone()));
+                            for (snap_setting, text) in [(SnapMoves(false), "Off"), (SnapMoves(true), "On")] {
+                                let mut entity = parent.spawn((
+                                    Button,
+                                    button_node.clone(),
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    snap_setting,
+                                ));
+                                entity.with_child((Text::new(text), button_text_style.clone()));
+                                if *snap_moves == snap_setting {
+                                    entity.insert(SelectedOption);
+                                }
+                            }
+                        });
+                    parent
+                        .spawn((
+                            Button,
+                            button_node,
+                            BackgroundColor(NORMAL_BUTTON),
+                            MenuButtonAction::BackToSettings,
+                        ))
+                        .with_child((Text::new("Back"), button_text_style));
+                });
+        });
+}
+
+fn controls_settings_menu_setup(
+    mut commands: Commands,
+    key_bindings: Res<KeyBindings>,
+    mut capture: ResMut<BindingCapture>,
+) {
+    capture.0 = None;
+
+    let button_node = Node {
+        width: Val::Px(160.0),
+        height: Val::Px(50.0),
+        margin: UiRect::all(Val::Px(8.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = (
+        TextFont {
+            font_size: 28.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    );
+    let row_node = Node {
+        align_items: AlignItems::Center,
+        justify_content: JustifyContent::Center,
+        flex_wrap: FlexWrap::Wrap,
+        max_width: Val::Px(700.0),
+        ..default()
+    };
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            OnControlsSettingsMenuScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(CRIMSON.into()),
+                ))
+                .with_children(|parent| {
+                    // Presets replace every key
+                    parent
+                        .spawn((row_node.clone(), BackgroundColor(CRIMSON.into())))
+                        .with_children(|parent| {
+                            for preset in ControlsPreset::ALL {
+                                parent
+                                    .spawn((
+                                        Button,
+                                        button_node.clone(),
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        preset,
+                                    ))
+                                    .with_child((Text::new(preset.name()), button_text_style.clone()));
+                            }
+                        });
+                    // Two columns of actions with their keys
+                    parent
+                        .spawn((row_node, BackgroundColor(CRIMSON.into())))
+                        .with_children(|parent| {
+                            for action in Action::ALL {
+                                parent
+                                    .spawn(Node {
+                                        align_items: AlignItems::Center,
+                                        justify_content: JustifyContent::End,
+                                        width: Val::Px(330.0),
+                                        ..default()
+                                    })
+                                    .with_children(|parent| {
+                                        parent.spawn((Text::new(action.name()), button_text_style.clone()));
+                                        let key = key_bindings.key(action).map(key_name).unwrap_or_default();
+                                        parent
+                                            .spawn((
+                                                Button,
+                                                button_node.clone(),
+                                                BackgroundColor(NORMAL_BUTTON),
+                                                BindingButton(action),
+                                            ))
+                                            .with_child((Text::new(key), button_text_style.clone(), BindingText(action)));
+                                    });
+                            }
+                        });
+                    parent.spawn((
+                        Text::new("Press a button to change its key"),
+                        button_text_style.clone(),
+                        Node {
+                            margin: UiRect::all(Val::Px(8.0)),
+                            ..default()
+                        },
+                        ControlsStatusText,
+                    ));
+                    parent
+                        .spawn((
+                            Button,
+                            button_node,
+                            BackgroundColor(NORMAL_BUTTON),
+                            MenuButtonAction::BackToSettings,
+                        ))
+                        .with_child((Text::new("Back"), button_text_style));
+                });
+        });
+}
+
+// This system applies presets and starts waiting for a key when a binding button is pressed
+#[allow(clippy::type_complexity)]
+fn controls_button_system(
+    interaction_query: Query<
+        (&Interaction, Option<&ControlsPreset>, Option<&BindingButton>),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut key_bindings: ResMut<KeyBindings>,
+    mut capture: ResMut<BindingCapture>,
+    mut status_text: Single<&mut Text, With<ControlsStatusText>>,
+) {
+    for (interaction, preset, binding_button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let Some(preset) = preset {
+            *key_bindings = KeyBindings::preset(*preset);
+            capture.0 = None;
+            status_text.0 = format!("{} controls", preset.name());
+        } else if let Some(BindingButton(action)) = binding_button {
+            capture.0 = Some(*action);
+            status_text.0 = format!("Press a key for {}, Escape cancels", action.name());
+        }
+    }
+}
+
+// This system binds the next pressed key to the action waiting for it, unless the key is taken
+fn capture_key_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut key_bindings: ResMut<KeyBindings>,
+    mut capture: ResMut<BindingCapture>,
+    mut status_text: Single<&mut Text, With<ControlsStatusText>>,
+) {
+    let (Some(action), Some(&key)) = (capture.0, keyboard_input.get_just_pressed().next()) else {
+        return;
+    };
+    if key == KeyCode::Escape {
+        capture.0 = None;
+        status_text.0 = String::from("Press a button to change its key");
+        return;
+    }
+
+    match key_bindings.bind(action, key) {
+        Ok(()) => {
+            capture.0 = None;
+            status_text.0 = format!("{} is now {}", action.name(), key_name(key));
+        }
+        // Still waiting for a free key
+        Err(BindingConflict::Action(other)) => {
+            status_text.0 = format!("{} is already {}, press another key", key_name(key), other.name());
+        }
+        Err(BindingConflict::Reserved) => {
+            status_text.0 = format!("{} is a key of the bot, press another key", key_name(key));
+        }
+    }
+}
+
+fn update_binding_text(
+    key_bindings: Res<KeyBindings>,
+    capture: Res<BindingCapture>,
+    mut text_query: Query<(&mut Text, &BindingText)>,
+) {
+    for (mut text, BindingText(action)) in &mut text_query {
+        text.0 = if capture.0 == Some(*action) {
+            String::from("...")
+        } else {
+            key_bindings.key(*action).map(key_name).unwrap_or_default()
+        };
+    }
+}
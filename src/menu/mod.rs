@@ -1,3 +1,4 @@
+pub mod focus;
 pub mod menu_mod;
 mod splash;
 
@@ -9,7 +10,7 @@ pub fn main_menu_plugin(app: &mut App) {
     app
         .insert_resource(Volume(7))
         .init_state::<AppState>()
-        .add_plugins((splash::splash_plugin, menu_mod::menu_plugin));
+        .add_plugins((splash::splash_plugin, menu_mod::menu_plugin, focus::focus_plugin));
 }
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug, States)]
@@ -24,8 +25,15 @@ pub enum AppState {
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
 pub enum MenuState {
     Main,
+    NewGame,
+    Statistics,
     Settings,
     SettingsSound,
+    SettingsMode,
+    SettingsTarget,
+    SettingsBoard,
+    SettingsInput,
+    SettingsControls,
     #[default]
     Disabled,
 }
@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+use std::io;
+
+/// Key-value backend to persist game data between launches
+pub trait Storage: Send + Sync {
+    /// Contents stored under `key`, `None` when nothing was stored yet or it can't be read
+    fn read(&self, key: &str) -> Option<String>;
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()>;
+
+    /// Removes contents under `key`, nothing stored is not an error
+    fn remove(&self, key: &str) -> io::Result<()>;
+}
+
+/// Resource with storage backend for current platform
+#[derive(Resource, Deref)]
+pub struct StorageResource(pub Box<dyn Storage>);
+
+impl Default for StorageResource {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn default() -> Self {
+        Self(Box::new(FileStorage::in_data_dir()))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn default() -> Self {
+        Self(Box::new(LocalStorage))
+    }
+}
+
+pub fn storage_plugin(app: &mut App) {
+    app.init_resource::<StorageResource>();
+}
+
+/// Keeps every key in memory, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStorage(std::sync::Mutex<std::collections::HashMap<String, String>>);
+
+#[cfg(test)]
+impl Storage for MemoryStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        self.0.lock().unwrap().get(key).cloned()
+    }
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()> {
+        self.0.lock().unwrap().insert(key.to_string(), contents.to_string());
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        self.0.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// Stores every key as separate file in a directory
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileStorage {
+    root: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Platform data directory (e.g. `~/.local/share/bevy_2048` on linux), falls back to working directory
+    pub fn in_data_dir() -> Self {
+        let root = dirs::data_dir()
+            .unwrap_or_else(|| std::path::PathBuf::from("."))
+            .join("bevy_2048");
+
+        Self::new(root)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.root.join(key)).ok()
+    }
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, contents)
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        match std::fs::remove_file(self.root.join(key)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Stores every key in browser local storage
+#[cfg(target_arch = "wasm32")]
+pub struct LocalStorage;
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorage {
+    fn storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage for LocalStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        Self::storage()?.get_item(key).ok()?
+    }
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()> {
+        let storage = Self::storage().ok_or_else(|| io::Error::other("local storage is not available"))?;
+
+        storage
+            .set_item(key, contents)
+            .map_err(|e| io::Error::other(format!("{:?}", e)))
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        let storage = Self::storage().ok_or_else(|| io::Error::other("local storage is not available"))?;
+
+        storage
+            .remove_item(key)
+            .map_err(|e| io::Error::other(format!("{:?}", e)))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_storage_test() {
+        let root = std::env::temp_dir().join(format!("bevy_2048_storage_test_{}", std::process::id()));
+        let storage = FileStorage::new(&root);
+
+        assert_eq!(storage.read("settings.ron"), None);
+        storage.write("settings.ron", "(version: 1)").unwrap();
+        assert_eq!(storage.read("settings.ron").as_deref(), Some("(version: 1)"));
+
+        storage.remove("settings.ron").unwrap();
+        assert_eq!(storage.read("settings.ron"), None);
+        // Nothing stored is not an error
+        storage.remove("settings.ron").unwrap();
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}